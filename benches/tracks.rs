@@ -0,0 +1,105 @@
+//! Benchmarks for track evaluation and deserialization.
+//!
+//! Run with `cargo bench`. This uses a small timing loop instead of a benchmark framework, so it
+//! builds without extra dependencies. Compare results between runs on the same machine.
+use byteorder::{WriteBytesExt, LE};
+use rust_rocket::interpolation::Interpolation;
+use rust_rocket::track::{Row, Track};
+use rust_rocket::RocketPlayer;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Run `f` repeatedly for about a second and print the mean time per iteration.
+fn bench(name: &str, mut f: impl FnMut()) {
+    // Warm up and estimate how many iterations fit in the measurement time
+    let start = Instant::now();
+    let mut iterations = 0u64;
+    while start.elapsed() < Duration::from_millis(100) {
+        f();
+        iterations += 1;
+    }
+
+    let iterations = iterations * 10;
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+    let mean = start.elapsed().div_f64(iterations as f64);
+    println!("{:<40} {:>12?}", name, mean);
+}
+
+/// A track with `keys` keys, 4 rows apart.
+fn sine_track(keys: Row) -> Track {
+    Track::from_fn(
+        "sine",
+        (0..keys).map(|i| i * 4),
+        Interpolation::Smooth,
+        |row| (row as f32 * 0.1).sin(),
+    )
+}
+
+/// Pseudo-random rows from `0` to `len`, the same for every run.
+fn random_rows(len: Row, count: usize) -> Vec<f32> {
+    let mut state = 0x2545_f491_u32;
+    (0..count)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (Row::from(state) % (len * 100)) as f32 / 100.
+        })
+        .collect()
+}
+
+/// Serialized data of `tracks` tracks with `keys` keys each.
+fn serialized_tracks(tracks: u64, keys: u32) -> Vec<u8> {
+    let mut data = vec![];
+    data.write_u64::<LE>(tracks).unwrap();
+    for t in 0..tracks {
+        let name = format!("group{}:track{}", t / 10, t);
+        data.write_u64::<LE>(name.len() as u64).unwrap();
+        data.extend_from_slice(name.as_bytes());
+        data.write_u64::<LE>(u64::from(keys)).unwrap();
+        for k in 0..keys {
+            data.write_u32::<LE>(k * 4).unwrap();
+            data.write_f32::<LE>(k as f32).unwrap();
+            data.write_u32::<LE>(k % 4).unwrap();
+        }
+    }
+    data
+}
+
+fn main() {
+    for &keys in &[16, 10_000] {
+        let track = sine_track(keys);
+        let len = keys * 4;
+
+        let mut row = 0.;
+        bench(&format!("get_value/{}_keys/sequential", keys), || {
+            black_box(track.get_value(black_box(row)));
+            row = (row + 0.25) % len as f32;
+        });
+
+        let rows = random_rows(len, 1024);
+        let mut i = 0;
+        bench(&format!("get_value/{}_keys/random", keys), || {
+            black_box(track.get_value(black_box(rows[i])));
+            i = (i + 1) % rows.len();
+        });
+    }
+
+    // Run with `--features simd` to compare with `bake_simd`
+    let track = sine_track(10_000);
+    bench("bake/10000_keys/100000_rows", || {
+        black_box(track.bake(black_box(0.), 0.4, 100_000));
+    });
+    #[cfg(feature = "simd")]
+    bench("bake_simd/10000_keys/100000_rows", || {
+        black_box(track.bake_simd(black_box(0.), 0.4, 100_000));
+    });
+
+    let data = serialized_tracks(500, 1000);
+    bench("deserialize/500_tracks_1000_keys", || {
+        black_box(RocketPlayer::deserialize(black_box(&data)));
+    });
+}
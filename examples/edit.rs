@@ -1,31 +1,23 @@
 use rust_rocket::client::{Event, RocketClient};
-use rust_rocket::track::RocketEngine;
+use rust_rocket::track::TrackSource;
 use std::error::Error;
-use std::fs::OpenOptions;
-use std::io::Write;
 use std::time::Duration;
 
 static TRACKS_FILE: &str = "tracks.bin";
 
 fn main() -> Result<(), Box<dyn Error>> {
     let mut rocket = RocketClient::new()?;
-    rocket.get_track_index_mut("test")?;
-    rocket.get_track_index_mut("test2")?;
-    rocket.get_track_index_mut("a:test2")?;
+    rocket.register_tracks(&["test", "test2", "a:test2"])?;
 
     let mut current_row = 0;
-    let mut paused = true;
 
     loop {
         if let Some(event) = rocket.poll_events()? {
             match event {
                 Event::SetRow(row) => {
                     println!("SetRow (row: {:?})", row);
-                    current_row = row;
                 }
-                Event::Pause(state) => {
-                    paused = state;
-
+                Event::Pause(_) => {
                     let track1 = rocket.get_track(rocket.get_track_index("test").unwrap());
                     println!(
                         "Pause (value: {:?}) (row: {:?})",
@@ -34,16 +26,9 @@ fn main() -> Result<(), Box<dyn Error>> {
                     );
                 }
                 Event::SaveTracks => {
-                    // Open a file for writing, create if not present,
-                    // truncate (overwrite) in case it has previous contents.
-                    let mut file = OpenOptions::new()
-                        .write(true)
-                        .create(true)
-                        .truncate(true)
-                        .open(TRACKS_FILE)?;
-
-                    // Serialize tracks into the file
-                    file.write_all(&rocket.serialize()).unwrap();
+                    // Serialize tracks into the file, replacing any previous contents.
+                    // A crash while saving can't leave a partially written file behind.
+                    rocket.save_tracks_atomic(TRACKS_FILE)?;
                     // See examples/play.rs for deserializing and playback
                     println!("Tracks saved to {}", TRACKS_FILE);
                 }
@@ -51,10 +36,7 @@ fn main() -> Result<(), Box<dyn Error>> {
             println!("{:?}", event);
         }
 
-        if !paused {
-            current_row += 1;
-            rocket.set_row(current_row)?;
-        }
+        current_row = rocket.tick(1)?;
 
         std::thread::sleep(Duration::from_millis(32));
     }
@@ -1,7 +1,6 @@
-use rust_rocket::track::RocketEngine;
+use rust_rocket::track::TrackSource;
 use rust_rocket::RocketPlayer;
 use std::error::Error;
-use std::fs::File;
 use std::time::Duration;
 
 static TRACKS_FILE: &str = "tracks.bin";
@@ -0,0 +1,67 @@
+//! This module contains [`AudioSync`] for syncing rows to audio playback.
+
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+/// The `AudioSync` Type. This converts between audio sample positions and rows.
+///
+/// Conversions are done in `f64`, so sample positions far into a long piece don't lose
+/// sub-row precision before being narrowed to the `f32` rows that
+/// [`Track::get_value`](crate::track::Track::get_value) takes.
+///
+/// # Examples
+///
+/// ```
+/// # use rust_rocket::audio::AudioSync;
+/// let sync = AudioSync::new(44100, 8.0);
+/// assert_eq!(sync.samples_to_row(44100 * 2), 16.0);
+/// assert_eq!(sync.row_to_samples(16.0), 44100 * 2);
+/// ```
+pub struct AudioSync {
+    /// Audio samples per second, per channel.
+    pub sample_rate: u32,
+    /// Rows per second.
+    pub rows_per_second: f64,
+}
+
+impl AudioSync {
+    /// Construct a new `AudioSync`.
+    pub fn new(sample_rate: u32, rows_per_second: f64) -> Self {
+        Self {
+            sample_rate,
+            rows_per_second,
+        }
+    }
+
+    /// Get the row being played at a sample position.
+    pub fn samples_to_row(&self, samples: u64) -> f32 {
+        self.samples_to_row_f64(samples) as f32
+    }
+
+    /// Get the row being played at a sample position, without narrowing to `f32`.
+    pub fn samples_to_row_f64(&self, samples: u64) -> f64 {
+        samples as f64 / f64::from(self.sample_rate) * self.rows_per_second
+    }
+
+    /// Get the sample position where `row` starts, e.g. for seeking audio after the tracker sets
+    /// the row.
+    ///
+    /// Negative rows give sample position `0`.
+    pub fn row_to_samples(&self, row: f64) -> u64 {
+        (row / self.rows_per_second * f64::from(self.sample_rate)).round() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_precision_at_large_positions() {
+        let sync = AudioSync::new(48000, 100.0);
+        // Ten days in, rows don't fit in f32 precisely anymore but f64 keeps up
+        let samples = 48000 * 60 * 60 * 24 * 10 + 240;
+        assert_eq!(sync.samples_to_row_f64(samples), 86_400_000.5);
+        assert_eq!(sync.row_to_samples(86_400_000.5), samples);
+        assert_eq!(sync.row_to_samples(-1.0), 0);
+    }
+}
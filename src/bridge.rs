@@ -0,0 +1,249 @@
+//! This module contains [`RocketBridge`] for mirroring edits between two trackers.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! # use rust_rocket::RocketClient;
+//! # use rust_rocket::bridge::RocketBridge;
+//! let local = RocketClient::new().unwrap();
+//! let remote = RocketClient::connect(("192.168.1.2", 1338)).unwrap();
+//! let mut bridge = RocketBridge::new(local, remote);
+//! loop {
+//!     bridge.pump().unwrap();
+//!     std::thread::sleep(std::time::Duration::from_millis(1));
+//! }
+//! ```
+use crate::client::{EditEvent, Error, Event, RocketClient};
+use crate::track::{Key, Row, TrackEditor, TrackSource};
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+/// How many relayed edits are remembered per client for recognizing echoes.
+const RELAY_MEMORY: usize = 256;
+
+/// A key edit by track name, so it can be compared across clients.
+#[derive(Clone, PartialEq)]
+struct Relayed {
+    track: String,
+    row: Row,
+    key: Option<Key>,
+}
+
+/// The `RocketBridge` Type. This connects two [`RocketClient`]s, and relays key edits and row
+/// changes received by each one to the other.
+///
+/// Tracks are matched by name. A track edited on one side is registered on the other side if
+/// needed, which makes that tracker send its keys for the track, so they're relayed back.
+///
+/// Edits and rows sent to a tracker may be echoed back. To avoid bouncing them between the
+/// trackers forever, the bridge remembers what it recently relayed to each client, and doesn't
+/// relay the same edit or row back. Edits that wouldn't change the other side's key aren't
+/// relayed either.
+///
+/// The bridge uses the clients' [edit callbacks](RocketClient::on_edit), replacing any callbacks
+/// registered before.
+pub struct RocketBridge {
+    clients: [RocketClient; 2],
+    edits: [Arc<Mutex<Vec<EditEvent>>>; 2],
+    relayed: [VecDeque<Relayed>; 2],
+    relayed_rows: [Option<Row>; 2],
+}
+
+impl RocketBridge {
+    /// Construct a new `RocketBridge` between two clients.
+    pub fn new(first: RocketClient, second: RocketClient) -> Self {
+        let mut clients = [first, second];
+        let edits: [Arc<Mutex<Vec<EditEvent>>>; 2] = Default::default();
+        for (client, edits) in clients.iter_mut().zip(&edits) {
+            let edits = Arc::clone(edits);
+            client.on_edit(move |edit| edits.lock().unwrap().push(edit));
+        }
+        Self {
+            clients,
+            edits,
+            relayed: [VecDeque::new(), VecDeque::new()],
+            relayed_rows: [None; 2],
+        }
+    }
+
+    /// Get the first client.
+    pub fn first(&self) -> &RocketClient {
+        &self.clients[0]
+    }
+
+    /// Get the second client.
+    pub fn second(&self) -> &RocketClient {
+        &self.clients[1]
+    }
+
+    /// Take the clients back. Their edit callbacks are replaced with ones that do nothing.
+    pub fn into_clients(self) -> (RocketClient, RocketClient) {
+        let [mut first, mut second] = self.clients;
+        first.on_edit(|_| ());
+        second.on_edit(|_| ());
+        (first, second)
+    }
+
+    /// Poll both clients, and relay everything received since the last call to the other one.
+    ///
+    /// Key edits and [`Event::SetRow`]s are relayed. Other events are skipped.
+    /// Call this fairly often, e.g. in a loop with a short sleep.
+    ///
+    /// # Errors
+    ///
+    /// This method can return an [`Error::IOError`] if either tracker disconnects.
+    pub fn pump(&mut self) -> Result<(), Error> {
+        for from in 0..2 {
+            loop {
+                let event = self.clients[from].poll_events()?;
+                // Edits received before the event are relayed first, to keep them in order
+                self.relay_edits(from)?;
+                match event {
+                    Some(Event::SetRow(row)) => self.relay_row(from, row)?,
+                    Some(_) => (),
+                    None => break,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn relay_edits(&mut self, from: usize) -> Result<(), Error> {
+        let edits = std::mem::take(&mut *self.edits[from].lock().unwrap());
+        for edit in edits {
+            let (track, row, key) = match edit {
+                EditEvent::KeySet { track, key } => (track, key.get_row(), Some(key)),
+                EditEvent::KeyDeleted { track, row } => (track, row, None),
+            };
+            let edit = Relayed {
+                track: self.clients[from].get_track(track).get_name().to_owned(),
+                row,
+                key,
+            };
+            self.relay_edit(from, edit)?;
+        }
+        Ok(())
+    }
+
+    fn relay_edit(&mut self, from: usize, edit: Relayed) -> Result<(), Error> {
+        if let Some(pos) = self.relayed[from].iter().position(|e| *e == edit) {
+            self.relayed[from].remove(pos);
+            return Ok(());
+        }
+
+        let to = 1 - from;
+        let client = &mut self.clients[to];
+        let track = client
+            .get_track_index_mut(&edit.track)
+            .map_err(Error::IOError)?;
+        if client.get_track(track).get_key(edit.row).copied() == edit.key {
+            return Ok(());
+        }
+        match edit.key {
+            Some(key) => client.set_key(track, key)?,
+            None => client.delete_key(track, edit.row)?,
+        }
+
+        let relayed = &mut self.relayed[to];
+        if relayed.len() >= RELAY_MEMORY {
+            relayed.pop_front();
+        }
+        relayed.push_back(edit);
+        Ok(())
+    }
+
+    fn relay_row(&mut self, from: usize, row: Row) -> Result<(), Error> {
+        if self.relayed_rows[from].take() == Some(row) {
+            return Ok(());
+        }
+        let to = 1 - from;
+        self.clients[to].set_row(row)?;
+        self.relayed_rows[to] = Some(row);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::tests::connect;
+    use crate::interpolation::Interpolation;
+
+    use std::{
+        io::{ErrorKind, Read, Write},
+        net::TcpStream,
+        time::Duration,
+    };
+
+    const SET_KEY: u8 = 0;
+    const GET_TRACK: u8 = 2;
+    const SET_ROW: u8 = 3;
+
+    /// Pump until the tracker receives `len` bytes, or return `None` after a while.
+    fn pump_read(
+        bridge: &mut RocketBridge,
+        tracker: &mut TcpStream,
+        len: usize,
+    ) -> Option<Vec<u8>> {
+        tracker
+            .set_read_timeout(Some(Duration::from_millis(10)))
+            .unwrap();
+        let mut buf = vec![0; len];
+        for _ in 0..20 {
+            bridge.pump().unwrap();
+            // A timeout may interrupt a read after it received some bytes, peek first
+            match tracker.peek(&mut buf) {
+                Ok(n) if n == len => {
+                    tracker.read_exact(&mut buf).unwrap();
+                    return Some(buf);
+                }
+                Ok(_) => (),
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => (),
+                Err(e) => panic!("{}", e),
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn relays_without_echoes() {
+        let (mut first, mut first_tracker) = connect();
+        first.get_track_index_mut("a").unwrap();
+        let mut get_track = [0; 1 + 4 + 1];
+        first_tracker.read_exact(&mut get_track).unwrap();
+        let (second, mut second_tracker) = connect();
+        let mut bridge = RocketBridge::new(first, second);
+
+        let set_key = [SET_KEY, 0, 0, 0, 0, 0, 0, 0, 3, 0x3f, 0x80, 0, 0, 1];
+        first_tracker.write_all(&set_key).unwrap();
+        assert_eq!(
+            pump_read(&mut bridge, &mut second_tracker, 1 + 4 + 1).unwrap(),
+            [GET_TRACK, 0, 0, 0, 1, b'a']
+        );
+        assert_eq!(
+            pump_read(&mut bridge, &mut second_tracker, set_key.len()).unwrap(),
+            set_key
+        );
+        let track = bridge.second().get_track_index("a").unwrap();
+        assert!(
+            bridge.second().get_track(track).get_key(3)
+                == Some(&Key::new(3, 1.0, Interpolation::Linear))
+        );
+
+        // The echo isn't relayed back, but the row is
+        second_tracker.write_all(&set_key).unwrap();
+        second_tracker.write_all(&[SET_ROW, 0, 0, 0, 5]).unwrap();
+        assert_eq!(
+            pump_read(&mut bridge, &mut first_tracker, 1 + 4).unwrap(),
+            [SET_ROW, 0, 0, 0, 5]
+        );
+
+        // Neither is the echoed row
+        first_tracker.write_all(&[SET_ROW, 0, 0, 0, 5]).unwrap();
+        assert!(pump_read(&mut bridge, &mut second_tracker, 1).is_none());
+        assert!(pump_read(&mut bridge, &mut first_tracker, 1).is_none());
+    }
+}
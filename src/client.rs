@@ -1,15 +1,29 @@
 //! This module contains the main client code, including the [`RocketClient`] type.
+use crate::audio::AudioSync;
+use crate::format;
+use crate::hash::FnvHasher;
+use crate::history::{Edit, EditHistory};
 use crate::interpolation::*;
 use crate::track::*;
 
-use byteorder::{LE, BigEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
     convert::TryFrom,
+    fs::File,
+    hash::Hasher,
     io::{Cursor, Read, Write},
     net::{TcpStream, ToSocketAddrs},
+    path::Path,
+    time::{Duration, Instant},
 };
 use thiserror::Error;
 
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
+#[cfg(windows)]
+use std::os::windows::io::{AsRawSocket, RawSocket};
+
 #[derive(Debug, Error)]
 /// The `Error` Type. This is the main error type.
 pub enum Error {
@@ -21,7 +35,7 @@ pub enum Error {
     /// Failure to transmit or receive greetings with the tracker
     Handshake(#[source] std::io::Error),
     #[error("The Rocket server greeting {0:?} wasn't correct")]
-    /// Handshake was performed but the the received greeting wasn't correct
+    /// Handshake was performed but the the received greeting didn't start correctly
     HandshakeGreetingMismatch([u8; 12]),
     #[error("Cannot set Rocket's TCP connection to nonblocking mode")]
     /// Error from [`TcpStream::set_nonblocking`]
@@ -29,8 +43,47 @@ pub enum Error {
     #[error("Rocket server disconnected")]
     /// Network IO error during operation
     IOError(#[source] std::io::Error),
+    #[error("Track names can't be empty")]
+    /// An empty track name was passed to [`RocketClient::register_tracks`]
+    EmptyTrackName,
+    #[error("Unknown command {0} from the Rocket server")]
+    /// The tracker sent a command that isn't part of the protocol, see
+    /// [`RocketClient::set_strict`]
+    UnknownCommand(u8),
+    #[error("Unknown track index {0} from the Rocket server")]
+    /// The tracker sent a key edit for a track that wasn't registered, see
+    /// [`RocketClient::set_strict`]
+    UnknownTrack(usize),
+    #[error("Invalid key from the Rocket server")]
+    /// The tracker sent a key with an unknown interpolation type, see
+    /// [`RocketClient::set_strict`]
+    InvalidInterpolation(#[source] InvalidInterpolation),
+    #[error("Row {0} doesn't fit in the Rocket protocol")]
+    /// A row beyond [`u32::MAX`] was to be sent to the tracker, see [`Row`]
+    RowOverflow(Row),
 }
 
+// Rocket protocol command ids
+const SET_KEY: u8 = 0;
+const DELETE_KEY: u8 = 1;
+const GET_TRACK: u8 = 2;
+const SET_ROW: u8 = 3;
+const PAUSE: u8 = 4;
+const SAVE_TRACKS: u8 = 5;
+
+/// How many rows sent with [`RocketClient::set_row`] are remembered for suppressing echoes
+const MAX_SENT_ROWS: usize = 16;
+
+/// How long after sending a row a received row can be its echo
+const ECHO_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// How long the client waits for [server info](RocketClient::server_info) after the greeting
+const SERVER_INFO_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// How many bytes are read from the tracker at once by default, see
+/// [`RocketClient::with_read_buffer_size`]
+const DEFAULT_READ_BUFFER_SIZE: usize = 4096;
+
 #[derive(Debug)]
 enum ClientState {
     New,
@@ -39,10 +92,14 @@ enum ClientState {
 }
 
 #[derive(Debug, Copy, Clone)]
+#[repr(C, u8)]
 /// The `Event` Type. These are the various events from the tracker.
+///
+/// `Event` has the layout of a C tagged union: a `u8` tag ordered as the variants are declared,
+/// followed by a union of the variant fields. This makes it safe to pass across an FFI boundary.
 pub enum Event {
     /// The tracker changes row.
-    SetRow(u32),
+    SetRow(Row),
     /// The tracker pauses or unpauses.
     Pause(bool),
     /// The tracker asks us to save our track data.
@@ -50,6 +107,165 @@ pub enum Event {
     SaveTracks,
 }
 
+impl Event {
+    /// Get the Rocket protocol command id of the message that produces this event.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::client::Event;
+    /// let event = Event::SetRow(42);
+    /// let copy = Event::from_command(event.command_byte(), event.payload());
+    /// assert!(matches!(copy, Some(Event::SetRow(42))));
+    /// ```
+    pub fn command_byte(&self) -> u8 {
+        match self {
+            Event::SetRow(_) => SET_ROW,
+            Event::Pause(_) => PAUSE,
+            Event::SaveTracks => SAVE_TRACKS,
+        }
+    }
+
+    /// Get the parameter of this event as a number.
+    ///
+    /// This is the row for [`Event::SetRow`], `1` or `0` for [`Event::Pause`] and `0` for
+    /// [`Event::SaveTracks`].
+    pub fn payload(&self) -> u64 {
+        match *self {
+            Event::SetRow(row) => row,
+            Event::Pause(flag) => flag as u64,
+            Event::SaveTracks => 0,
+        }
+    }
+
+    /// Construct an event from a protocol command id and a [payload](Event::payload).
+    ///
+    /// Returns `None` if `command` doesn't produce an event.
+    pub fn from_command(command: u8, payload: u64) -> Option<Event> {
+        match command {
+            SET_ROW => Some(Event::SetRow(payload)),
+            PAUSE => Some(Event::Pause(payload == 1)),
+            SAVE_TRACKS => Some(Event::SaveTracks),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+/// The `PollOutcome` Type. This is the result of [`RocketClient::poll_events_detailed`].
+pub enum PollOutcome {
+    /// An event was received.
+    Event(Event),
+    /// Nothing was received.
+    Idle,
+    /// Data was received, but no event. Key edits may have been applied, or the rest of a
+    /// command is still in flight.
+    Progressing,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// The `SyncState` Type. This is the result of [`RocketClient::sync`].
+pub struct SyncState {
+    /// The current row.
+    pub row: Row,
+    /// Whether the tracker is paused.
+    pub paused: bool,
+    /// Whether the tracker asked us to save our track data.
+    pub saved: bool,
+    /// Whether any key edits were received.
+    pub edited: bool,
+}
+
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+/// The `EditEvent` Type. These are the key edits received from the tracker.
+///
+/// See [`RocketClient::on_edit`].
+pub enum EditEvent {
+    /// A key was inserted or updated on the track with this index.
+    KeySet { track: usize, key: Key },
+    /// The key at `row` was deleted from the track with this index.
+    KeyDeleted { track: usize, row: Row },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// The `Direction` Type. This tells which way a message was going, see
+/// [`RocketClient::set_packet_logger`].
+pub enum Direction {
+    /// The message was sent to the tracker.
+    Sent,
+    /// The message was received from the tracker.
+    Received,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// The `LoggedCommand` Type. These are the decoded commands received from the tracker, see
+/// [`RocketClient::set_command_history`].
+pub enum LoggedCommand {
+    /// Set a key. `interpolation` is the raw byte sent by the tracker.
+    SetKey {
+        track: usize,
+        row: u32,
+        value: f32,
+        interpolation: u8,
+    },
+    /// Delete the key at `row`.
+    DeleteKey { track: usize, row: u32 },
+    /// Change the row.
+    SetRow(u32),
+    /// Pause or unpause.
+    Pause(bool),
+    /// Save track data.
+    SaveTracks,
+    /// A command with an unknown id.
+    Unknown(u8),
+}
+
+/// The last received commands, oldest first.
+///
+/// Commands are pushed to a `Vec` of up to twice the capacity, which is halved when full, so
+/// the latest commands are always a contiguous slice.
+#[derive(Default)]
+struct CommandHistory {
+    commands: Vec<LoggedCommand>,
+    capacity: usize,
+}
+
+impl CommandHistory {
+    fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        let excess = self.commands.len().saturating_sub(capacity);
+        self.commands.drain(..excess);
+        self.commands.shrink_to(capacity * 2);
+    }
+
+    fn push(&mut self, command: LoggedCommand) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.commands.len() == self.capacity * 2 {
+            self.commands.drain(..self.capacity);
+        }
+        self.commands.push(command);
+    }
+
+    fn as_slice(&self) -> &[LoggedCommand] {
+        &self.commands[self.commands.len().saturating_sub(self.capacity)..]
+    }
+}
+
+#[derive(Clone, Default)]
+/// The `Snapshot` Type. This is a copy of a client's tracks, see
+/// [`RocketClient::serialize_delta`].
+///
+/// The default snapshot has no tracks, so a delta since it contains all keys.
+pub struct Snapshot {
+    tracks: Vec<Track>,
+}
+
+type PacketLogger = Box<dyn FnMut(Direction, &[u8]) + Send>;
+type WarningCallback = Box<dyn FnMut(&Error) + Send>;
+
 enum ReceiveResult {
     Some(Event),
     None,
@@ -57,30 +273,58 @@ enum ReceiveResult {
 }
 
 /// The `RocketClient` type. This contains the connected socket and other fields.
+///
+/// # Track registration
+///
+/// The Rocket protocol doesn't acknowledge GET_TRACK messages. Tracks are added locally as soon
+/// as they're requested, and the tracker is assumed to accept them and send their keys. A track
+/// is [pending](RocketClient::pending_tracks) until the first key edit for it is received, which
+/// confirms that the tracker knows it by the same index. Tracks without keys in the tracker stay
+/// pending, so a pending track isn't necessarily rejected.
 pub struct RocketClient {
     stream: TcpStream,
     state: ClientState,
     cmd: Vec<u8>,
+    read_buffer: Box<[u8]>,
+    read_buffer_start: usize,
+    read_buffer_end: usize,
     tracks: Vec<Track>,
+    pending_tracks: BTreeSet<usize>,
+    row: Row,
+    paused: bool,
+    sent_rows: VecDeque<(Row, Instant)>,
+    edited: bool,
+    strict: bool,
+    rows_per_second: Option<f64>,
+    pending_events: VecDeque<Event>,
+    history: Option<EditHistory>,
+    edit_callback: Option<Box<dyn FnMut(EditEvent) + Send>>,
+    warning_callback: Option<WarningCallback>,
+    packet_logger: Option<PacketLogger>,
+    command_history: CommandHistory,
+    server_info: String,
 }
 
-impl RocketEngine for RocketClient {
+impl TrackSource for RocketClient {
       /// Get track by name.
     ///
-    /// You should use [`get_track_mut`](RocketClient::get_track_mut) to create a track.
+    /// You should use [`get_track_index_mut`](TrackEditor::get_track_index_mut) to create a track.
     /// 
     fn get_track_index(&self, name: &str) -> Option<usize> {
         self.tracks.iter().enumerate().find(|t| t.1.get_name() == name).map(|t| t.0)
     }
     fn get_track(&self, index: usize) ->&Track { &self.tracks[index] }
+}
 
+impl TrackEditor for RocketClient {
     /// Get track by name.
     ///
     /// If the track does not yet exist it will be created.
     ///
     /// # Errors
     ///
-    /// This method can return an [`Error::IOError`] if Rocket tracker disconnects.
+    /// This method can return an [`Error::IOError`] if Rocket tracker disconnects, or an error
+    /// of kind [`std::io::ErrorKind::InvalidInput`] if `name` is empty.
     ///
     /// # Panics
     ///
@@ -90,7 +334,7 @@ impl RocketEngine for RocketClient {
     ///
     /// ```rust,no_run
     /// # use rust_rocket::RocketClient;
-    /// # use crate::rust_rocket::track::RocketEngine;
+    /// # use crate::rust_rocket::track::{TrackEditor, TrackSource};
     /// # let mut rocket = RocketClient::new().unwrap();
     /// let track_index = rocket.get_track_index_mut("namespace:track").unwrap();
     /// let track = rocket.get_track(track_index);
@@ -104,20 +348,55 @@ impl RocketEngine for RocketClient {
             .find(|(_, t)| t.get_name() == name)
         {
             Ok(i)
+        } else if name.is_empty() {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                Error::EmptyTrackName,
+            ))
         } else {
-            // Send GET_TRACK message
-            let mut buf = vec![2];
-            buf.write_u32::<BigEndian>(u32::try_from(name.len()).expect("Track name too long"))
-                .unwrap_or_else(|_|
-                // Can writes to a vec fail? Consider changing to unreachable_unchecked in 1.0
-                unreachable!());
-            buf.extend_from_slice(&name.as_bytes());
-            self.stream.write_all(&buf)?;
+            self.send(&get_track_message(name))?;
 
+            self.pending_tracks.insert(self.tracks.len());
             self.tracks.push(Track::new(name));
             Ok(self.tracks.len() - 1)
         }
     }
+
+    /// Insert or update a key on a track, and send it to the tracker.
+    ///
+    /// This is [`RocketClient::set_key`], with the error as a [`std::io::Error`].
+    fn set_key(&mut self, track: usize, key: Key) -> Result<(), std::io::Error> {
+        RocketClient::set_key(self, track, key).map_err(|e| match e {
+            Error::IOError(e) => e,
+            e => std::io::Error::other(e),
+        })
+    }
+}
+
+#[cfg(unix)]
+impl AsRawFd for RocketClient {
+    /// Get the file descriptor of the connection to the tracker, e.g. for registering it with an
+    /// event loop such as `mio`. When it's readable, call
+    /// [`poll_events`](RocketClient::poll_events) until it returns `None`, as received data is
+    /// buffered by the client.
+    ///
+    /// See [`RocketClient::get_ref`] for what not to do with it.
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+}
+
+#[cfg(windows)]
+impl AsRawSocket for RocketClient {
+    /// Get the socket of the connection to the tracker, e.g. for registering it with an event
+    /// loop such as `mio`. When it's readable, call
+    /// [`poll_events`](RocketClient::poll_events) until it returns `None`, as received data is
+    /// buffered by the client.
+    ///
+    /// See [`RocketClient::get_ref`] for what not to do with it.
+    fn as_raw_socket(&self) -> RawSocket {
+        self.stream.as_raw_socket()
+    }
 }
 
 impl RocketClient {
@@ -162,10 +441,28 @@ impl RocketClient {
             stream,
             state: ClientState::New,
             cmd: Vec::new(),
+            read_buffer: vec![0; DEFAULT_READ_BUFFER_SIZE].into_boxed_slice(),
+            read_buffer_start: 0,
+            read_buffer_end: 0,
             tracks: Vec::new(),
+            pending_tracks: BTreeSet::new(),
+            row: 0,
+            paused: true,
+            sent_rows: VecDeque::new(),
+            edited: false,
+            strict: false,
+            rows_per_second: None,
+            pending_events: VecDeque::new(),
+            history: None,
+            edit_callback: None,
+            warning_callback: None,
+            packet_logger: None,
+            command_history: CommandHistory::default(),
+            server_info: String::new(),
         };
 
         rocket.handshake()?;
+        rocket.read_server_info()?;
 
         rocket
             .stream
@@ -175,20 +472,434 @@ impl RocketClient {
         Ok(rocket)
     }
 
-    /// Send a SetRow message.
+    /// Reserve room for `capacity` tracks in total, so registering that many doesn't reallocate.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use rust_rocket::RocketClient;
+    /// let mut rocket = RocketClient::new().unwrap().with_track_capacity(200);
+    /// ```
+    pub fn with_track_capacity(mut self, capacity: usize) -> Self {
+        self.tracks
+            .reserve(capacity.saturating_sub(self.tracks.len()));
+        self
+    }
+
+    /// Set how many bytes are read from the tracker at once. The default is 4096.
+    ///
+    /// Received data is read into a buffer of this size, and commands are parsed from the
+    /// buffer, so a burst of commands, e.g. the keys sent after registering tracks, only takes a
+    /// few reads. Data that was already received is kept.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `size` is `0`.
+    pub fn with_read_buffer_size(mut self, size: usize) -> Self {
+        assert!(size > 0, "Read buffer size must be positive");
+        let buffered = &self.read_buffer[self.read_buffer_start..self.read_buffer_end];
+        let mut read_buffer = vec![0; size.max(buffered.len())];
+        read_buffer[..buffered.len()].copy_from_slice(buffered);
+        self.read_buffer_start = 0;
+        self.read_buffer_end = buffered.len();
+        self.read_buffer = read_buffer.into_boxed_slice();
+        self
+    }
+
+    /// Get multiple tracks by name, creating the ones that don't exist yet.
+    ///
+    /// This works like calling
+    /// [`get_track_index_mut`](TrackEditor::get_track_index_mut) for each name in order, but
+    /// requests all new tracks from the tracker with a single write. The returned indices are in
+    /// the same order as `names`.
+    ///
+    /// # Errors
+    ///
+    /// This method can return an [`Error::IOError`] if Rocket tracker disconnects, or
+    /// [`Error::EmptyTrackName`] if a name is empty. No tracks are registered in that case.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the length of a name exceeds [`u32::MAX`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use rust_rocket::RocketClient;
+    /// # let mut rocket = RocketClient::new().unwrap();
+    /// let indices = rocket.register_tracks(&["camera:x", "camera:y"]).unwrap();
+    /// ```
+    pub fn register_tracks(&mut self, names: &[&str]) -> Result<Vec<usize>, Error> {
+        if names.iter().any(|name| name.is_empty()) {
+            return Err(Error::EmptyTrackName);
+        }
+        let mut new_names: Vec<&str> = Vec::new();
+        let mut indices = Vec::with_capacity(names.len());
+        for name in names {
+            let index = match self.get_track_index(name) {
+                Some(index) => index,
+                None => {
+                    let new = match new_names.iter().position(|new| new == name) {
+                        Some(new) => new,
+                        None => {
+                            new_names.push(name);
+                            new_names.len() - 1
+                        }
+                    };
+                    self.tracks.len() + new
+                }
+            };
+            indices.push(index);
+        }
+
+        // Only add the tracks once the tracker has been told about them
+        let messages: Vec<Vec<u8>> = new_names
+            .iter()
+            .map(|name| get_track_message(name))
+            .collect();
+        self.send_all(&messages).map_err(Error::IOError)?;
+        for name in new_names {
+            self.pending_tracks.insert(self.tracks.len());
+            self.tracks.push(Track::new(name));
+        }
+        Ok(indices)
+    }
+
+    /// Request all tracks from the tracker again, keeping their indices.
     ///
-    /// This changes the current row on the tracker side.
+    /// GET_TRACK messages are sent for all tracks in index order with a single write, which
+    /// makes the tracker assign the same indices and send all keys again. The local keys are
+    /// removed first, so afterwards the tracks only contain the tracker's keys. No local tracks
+    /// are added. This is useful when the tracker's tracks can't be trusted anymore, e.g. after
+    /// it reloaded its project. All tracks are [pending](RocketClient::pending_tracks) again.
     ///
     /// # Errors
     ///
     /// This method can return an [`Error::IOError`] if Rocket tracker disconnects.
-    pub fn set_row(&mut self, row: u32) -> Result<(), Error> {
+    pub fn resync_tracks(&mut self) -> Result<(), Error> {
+        let messages: Vec<Vec<u8>> = self
+            .tracks
+            .iter()
+            .map(|t| get_track_message(t.get_name()))
+            .collect();
+        self.send_all(&messages).map_err(Error::IOError)?;
+        for track in &mut self.tracks {
+            track.clear_keys();
+        }
+        self.pending_tracks = (0..self.tracks.len()).collect();
+        Ok(())
+    }
+
+    /// Get the information a tracker sent after its greeting, such as a version or capabilities.
+    ///
+    /// Standard trackers only send `hello, demo!`, which gives an empty string. Trackers can
+    /// append printable ASCII to the greeting, which is returned without surrounding whitespace.
+    /// It must arrive within 50 ms of the greeting, so connecting waits that long for it, or until
+    /// a command arrives.
+    pub fn server_info(&self) -> &str {
+        &self.server_info
+    }
+
+    /// Get a reference to the connection to the tracker, e.g. for setting socket options or
+    /// registering it with an event loop.
+    ///
+    /// The stream is in nonblocking mode, which the client relies on. Reading from or writing to
+    /// it bypasses the protocol handling, and will desync the client from the tracker.
+    pub fn get_ref(&self) -> &TcpStream {
+        &self.stream
+    }
+
+    /// Get the indices of tracks that the tracker hasn't confirmed yet, in ascending order.
+    ///
+    /// See [Track registration](RocketClient#track-registration) for when tracks are confirmed.
+    pub fn pending_tracks(&self) -> impl Iterator<Item = usize> + '_ {
+        self.pending_tracks.iter().copied()
+    }
+
+    /// Send a SetRow message.
+    ///
+    /// This changes the current row on the tracker side, and the
+    /// [current row](RocketClient::current_row) of the client, so both agree.
+    ///
+    /// Some trackers echo the row back. Echoes are suppressed, so [`Event::SetRow`] is only
+    /// returned for rows set on the tracker side, e.g. when scrubbing. A received row counts as an
+    /// echo if it's the oldest row sent and not echoed yet, and was sent less than 250 ms ago; any
+    /// other row means the tracker changed the row itself, which cancels all pending echoes.
+    /// Trackers that don't echo never send these rows back, so the time limit keeps them from
+    /// suppressing a later row set on the tracker side.
+    ///
+    /// # Errors
+    ///
+    /// This method can return an [`Error::IOError`] if Rocket tracker disconnects, or an
+    /// [`Error::RowOverflow`] if `row` exceeds [`u32::MAX`].
+    pub fn set_row(&mut self, row: Row) -> Result<(), Error> {
         // Send SET_ROW message
-        let mut buf = vec![3];
-        buf.write_u32::<BigEndian>(row).unwrap_or_else(|_|
+        let mut buf = vec![SET_ROW];
+        let wire = wire_row(row)?;
+        buf.write_u32::<BigEndian>(wire).unwrap_or_else(|_|
                 // Can writes to a vec fail? Consider changing to unreachable_unchecked in 1.0
                 unreachable!());
-        self.stream.write_all(&buf).map_err(Error::IOError)
+        self.send(&buf).map_err(Error::IOError)?;
+        self.row = row;
+        // Trackers that don't echo never clear this, so only remember the latest rows
+        if self.sent_rows.len() == MAX_SENT_ROWS {
+            self.sent_rows.pop_front();
+        }
+        self.sent_rows.push_back((row, Instant::now()));
+        Ok(())
+    }
+
+    /// Jump to `row`, e.g. for previewing an arbitrary row.
+    ///
+    /// This is [`set_row`](RocketClient::set_row), named for intent: the row is sent to the
+    /// tracker and becomes the [current row](RocketClient::current_row), which
+    /// [`tick`](RocketClient::tick) then advances from, if not paused.
+    ///
+    /// # Errors
+    ///
+    /// This method can return an [`Error::IOError`] if Rocket tracker disconnects, or an
+    /// [`Error::RowOverflow`] if `row` exceeds [`u32::MAX`].
+    pub fn seek(&mut self, row: Row) -> Result<(), Error> {
+        self.set_row(row)
+    }
+
+    /// Get the current row: the latest row set with [`set_row`](RocketClient::set_row),
+    /// [`seek`](RocketClient::seek) or [`tick`](RocketClient::tick), or received from the
+    /// tracker.
+    pub fn current_row(&self) -> Row {
+        self.row
+    }
+
+    /// Pause or unpause playback on the client side.
+    ///
+    /// The Rocket protocol only has PAUSE messages from the tracker to clients, and GNU Rocket
+    /// trackers can't parse one from a client, so nothing is sent. This only changes the pause
+    /// state used by [`tick`](RocketClient::tick) and [`sync`](RocketClient::sync), until the
+    /// tracker sends its own. The tracker follows the rows sent while unpaused locally, so a tool
+    /// can still drive playback.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Set the row rate of the demo.
+    ///
+    /// The Rocket protocol doesn't carry the row rate, so trackers can't tell it to clients.
+    /// Setting it here gives one place to read it from, see
+    /// [`rows_per_second`](RocketClient::rows_per_second) and
+    /// [`audio_sync`](RocketClient::audio_sync).
+    pub fn set_rows_per_second(&mut self, rows_per_second: f64) {
+        self.rows_per_second = Some(rows_per_second);
+    }
+
+    /// Get the row rate set with [`set_rows_per_second`](RocketClient::set_rows_per_second).
+    pub fn rows_per_second(&self) -> Option<f64> {
+        self.rows_per_second
+    }
+
+    /// Get an [`AudioSync`] for audio at `sample_rate` and the row rate set with
+    /// [`set_rows_per_second`](RocketClient::set_rows_per_second), or `None` if it's not set.
+    pub fn audio_sync(&self, sample_rate: u32) -> Option<AudioSync> {
+        self.rows_per_second
+            .map(|rows_per_second| AudioSync::new(sample_rate, rows_per_second))
+    }
+
+    /// Insert or update a key on a track, and send it to the tracker.
+    ///
+    /// GNU Rocket trackers only send keys to clients, so this is mostly useful with trackers
+    /// that accept SET_KEY messages from clients. The local track is updated either way.
+    ///
+    /// # Errors
+    ///
+    /// This method can return an [`Error::IOError`] if Rocket tracker disconnects, or an
+    /// [`Error::RowOverflow`] if the key's row exceeds [`u32::MAX`].
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `track` is not a valid track index.
+    pub fn set_key(&mut self, track: usize, key: Key) -> Result<(), Error> {
+        self.edit(track, key.get_row(), Some(key))
+    }
+
+    /// Delete a key from a track, and send the deletion to the tracker.
+    ///
+    /// See [`set_key`](RocketClient::set_key) for caveats.
+    ///
+    /// # Errors
+    ///
+    /// This method can return an [`Error::IOError`] if Rocket tracker disconnects, or an
+    /// [`Error::RowOverflow`] if `row` exceeds [`u32::MAX`].
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `track` is not a valid track index.
+    pub fn delete_key(&mut self, track: usize, row: Row) -> Result<(), Error> {
+        self.edit(track, row, None)
+    }
+
+    /// Insert or update a key on a local track only, without sending it to the tracker.
+    ///
+    /// This stages edits, e.g. while dragging a key, to be sent in bulk later with
+    /// [`commit_track`](RocketClient::commit_track). Until then the local track differs from the
+    /// tracker's; keys received from the tracker still overwrite staged keys at the same row.
+    /// Staged keys aren't recorded in the [history](RocketClient::enable_history).
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `track` is not a valid track index.
+    pub fn set_key_local(&mut self, track: usize, key: Key) {
+        self.tracks[track].set_key(key);
+    }
+
+    /// Send all keys of a local track to the tracker with a single write.
+    ///
+    /// This pushes keys staged with [`set_key_local`](RocketClient::set_key_local). Keys that
+    /// only exist in the tracker are not deleted.
+    ///
+    /// # Errors
+    ///
+    /// This method can return an [`Error::IOError`] if Rocket tracker disconnects, or an
+    /// [`Error::RowOverflow`] if a key's row exceeds [`u32::MAX`]. Nothing is sent in that case.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `track` is not a valid track index.
+    pub fn commit_track(&mut self, track: usize) -> Result<(), Error> {
+        let track_id = u32::try_from(track).expect("Track index too large");
+        let messages = self.tracks[track]
+            .keys()
+            .iter()
+            .map(|key| set_key_message(track_id, key))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.send_all(&messages).map_err(Error::IOError)
+    }
+
+    /// Flush messages sent to the tracker.
+    ///
+    /// Messages are written to the connection as soon as they're sent, without buffering in the
+    /// client, so this only forwards to the stream's [`flush`](Write::flush). Call it after
+    /// batched sends such as [`commit_track`](RocketClient::commit_track), before expecting the
+    /// tracker to reflect them, so the code keeps working if sends are buffered in the future.
+    /// The kernel may still delay small writes; disable that with
+    /// [`TcpStream::set_nodelay`] through [`get_ref`](RocketClient::get_ref) for deterministic
+    /// timing, e.g. in tests.
+    ///
+    /// # Errors
+    ///
+    /// This method can return an [`Error::IOError`] if Rocket tracker disconnects.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.stream.flush().map_err(Error::IOError)
+    }
+
+    /// Start recording edits made with [`set_key`](RocketClient::set_key) and
+    /// [`delete_key`](RocketClient::delete_key), so that they can be undone.
+    ///
+    /// Edits received from the tracker are not recorded.
+    pub fn enable_history(&mut self) {
+        self.history.get_or_insert_with(EditHistory::new);
+    }
+
+    /// Get the edit history, if it's enabled.
+    pub fn history(&self) -> Option<&EditHistory> {
+        self.history.as_ref()
+    }
+
+    /// Revert the latest recorded edit, and send the reverting edit to the tracker.
+    ///
+    /// Returns `false` if there was nothing to undo.
+    ///
+    /// # Errors
+    ///
+    /// This method can return an [`Error::IOError`] if Rocket tracker disconnects.
+    pub fn undo(&mut self) -> Result<bool, Error> {
+        let edit = match self.history.as_ref().and_then(EditHistory::next_undo) {
+            Some(edit) => edit,
+            None => return Ok(false),
+        };
+        // The edit stays in the history if it couldn't be sent
+        self.apply_edit(edit)?;
+        self.history.as_mut().unwrap().undone();
+        Ok(true)
+    }
+
+    /// Apply the latest undone edit again, and send it to the tracker.
+    ///
+    /// Returns `false` if there was nothing to redo.
+    ///
+    /// # Errors
+    ///
+    /// This method can return an [`Error::IOError`] if Rocket tracker disconnects.
+    pub fn redo(&mut self) -> Result<bool, Error> {
+        let edit = match self.history.as_ref().and_then(EditHistory::next_redo) {
+            Some(edit) => edit,
+            None => return Ok(false),
+        };
+        self.apply_edit(edit)?;
+        self.history.as_mut().unwrap().redone();
+        Ok(true)
+    }
+
+    fn edit(&mut self, track: usize, row: Row, key: Option<Key>) -> Result<(), Error> {
+        let edit = Edit {
+            track,
+            row,
+            old: self.tracks[track].get_key(row).copied(),
+            new: key,
+        };
+        self.apply_edit(edit)?;
+        if let Some(history) = &mut self.history {
+            history.record(edit);
+        }
+        Ok(())
+    }
+
+    fn apply_edit(&mut self, edit: Edit) -> Result<(), Error> {
+        let track_id = u32::try_from(edit.track).expect("Track index too large");
+        match edit.new {
+            Some(key) => {
+                self.send(&set_key_message(track_id, &key)?)
+                    .map_err(Error::IOError)?;
+                self.tracks[edit.track].set_key(key);
+            }
+            None => {
+                self.send(&delete_key_message(track_id, edit.row)?)
+                    .map_err(Error::IOError)?;
+                self.tracks[edit.track].delete_key(edit.row);
+            }
+        }
+        Ok(())
+    }
+
+    /// Advance the current row, unless the tracker is paused.
+    ///
+    /// The client keeps track of the row and pause state it has received from the tracker.
+    /// When not paused, this adds `delta_rows` to the current row and sends it to the tracker
+    /// with [`set_row`](RocketClient::set_row). When paused, nothing is sent.
+    ///
+    /// Returns the current row after advancing.
+    ///
+    /// # Errors
+    ///
+    /// This method can return an [`Error::IOError`] if Rocket tracker disconnects, or an
+    /// [`Error::RowOverflow`] if the row passes [`u32::MAX`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use rust_rocket::RocketClient;
+    /// # let mut rocket = RocketClient::new().unwrap();
+    /// loop {
+    ///     while let Some(event) = rocket.poll_events().unwrap() {
+    ///         // Do something with the various events.
+    ///     }
+    ///     let row = rocket.tick(1).unwrap();
+    ///     // Render the frame at `row`.
+    /// }
+    /// ```
+    pub fn tick(&mut self, delta_rows: Row) -> Result<Row, Error> {
+        if !self.paused {
+            self.set_row(self.row.saturating_add(delta_rows))?;
+        }
+        Ok(self.row)
     }
 
     /// Poll for new events from the tracker.
@@ -197,6 +908,10 @@ impl RocketClient {
     /// You should call this fairly often your main loop.
     /// It is recommended to keep calling this as long as your receive `Some(Event)`.
     ///
+    /// Key edits don't produce events. They are applied as they are received, and polling goes
+    /// on until an event is received or no more data is available, so `Ok(None)` means the
+    /// tracker has nothing more to say for now.
+    ///
     /// # Errors
     ///
     /// This method can return an [`Error::IOError`] if Rocket tracker disconnects.
@@ -224,109 +939,613 @@ impl RocketClient {
         }
     }
 
-    /// Serialize current tracks as bytes
-    /// Tracks can be turned into a [`RocketPlayer`](crate::RocketPlayer::deserialize) for playback.
-    pub fn serialize(&self) -> Vec<u8> {
-        let mut wtr = vec![];
-        wtr.write_u64::<LE>(self.tracks.len() as u64).unwrap();
-        for t in self.tracks.iter() {
-            t.serialize(&mut wtr);
+    /// Poll for new events from the tracker, skipping rows that are immediately replaced.
+    ///
+    /// This works like [`poll_events`](RocketClient::poll_events), but consecutive
+    /// [`Event::SetRow`]s are collapsed into the last one, e.g. while the playhead is dragged.
+    /// Other events are never skipped, and stay in order with the rows: rows before a
+    /// [`Event::Pause`] or [`Event::SaveTracks`] are returned first, then the other event on the
+    /// next call.
+    ///
+    /// Key edits are applied as they are received, so when a row is returned, all edits that
+    /// were received before it have been applied, and possibly some that came after it.
+    ///
+    /// # Errors
+    ///
+    /// This method can return an [`Error::IOError`] if Rocket tracker disconnects.
+    pub fn poll_latest_row(&mut self) -> Result<Option<Event>, Error> {
+        let mut row = None;
+        while let Some(event) = self.poll_events()? {
+            match event {
+                Event::SetRow(_) => row = Some(event),
+                event if row.is_some() => {
+                    self.pending_events.push_back(event);
+                    break;
+                }
+                event => return Ok(Some(event)),
+            }
         }
-        wtr
+        Ok(row)
     }
 
-    fn poll_event(&mut self) -> Result<ReceiveResult, Error> {
-        match self.state {
-            ClientState::New => {
-                let mut buf = [0; 1];
-                match self.stream.read_exact(&mut buf) {
-                    Ok(()) => {
-                        self.cmd.extend_from_slice(&buf);
-                        match self.cmd[0] {
-                            0 => self.state = ClientState::Incomplete(4 + 4 + 4 + 1), //SET_KEY
-                            1 => self.state = ClientState::Incomplete(4 + 4),         //DELETE_KEY
-                            3 => self.state = ClientState::Incomplete(4),             //SET_ROW
-                            4 => self.state = ClientState::Incomplete(1),             //PAUSE
-                            5 => self.state = ClientState::Complete,                  //SAVE_TRACKS
-                            _ => self.state = ClientState::Complete, // Error / Unknown
-                        }
-                        Ok(ReceiveResult::Incomplete)
-                    }
-                    Err(e) => match e.kind() {
-                        std::io::ErrorKind::WouldBlock => Ok(ReceiveResult::None),
-                        _ => Err(Error::IOError(e)),
-                    },
-                }
-            }
-            ClientState::Incomplete(bytes) => {
-                let mut buf = vec![0; bytes];
-                match self.stream.read(&mut buf) {
-                    Ok(bytes_read) => {
-                        self.cmd.extend_from_slice(&buf);
-                        if bytes - bytes_read > 0 {
-                            self.state = ClientState::Incomplete(bytes - bytes_read);
-                        } else {
-                            self.state = ClientState::Complete;
-                        }
-                        Ok(ReceiveResult::Incomplete)
+    /// Wait until the tracker sets the row, e.g. to start at the tracker's position.
+    ///
+    /// This polls the tracker, sleeping briefly between polls, until an [`Event::SetRow`] is
+    /// received or `timeout` elapses. Returns the row, or `None` on timeout.
+    ///
+    /// Key edits received in the meantime are applied as with
+    /// [`poll_events`](RocketClient::poll_events). Other events received in the meantime, such as
+    /// [`Event::SaveTracks`], are kept and returned by the next calls to `poll_events`.
+    ///
+    /// # Errors
+    ///
+    /// This method can return an [`Error::IOError`] if Rocket tracker disconnects.
+    pub fn wait_for_row(&mut self, timeout: Duration) -> Result<Option<Row>, Error> {
+        let start = Instant::now();
+        let mut skipped = Vec::new();
+        let result = loop {
+            match self.poll_events() {
+                Ok(Some(Event::SetRow(row))) => break Ok(Some(row)),
+                Ok(Some(event)) => skipped.push(event),
+                Ok(None) => match timeout.checked_sub(start.elapsed()) {
+                    Some(remaining) if remaining > Duration::ZERO => {
+                        std::thread::sleep(remaining.min(Duration::from_millis(1)))
+                    }
+                    _ => break Ok(None),
+                },
+                Err(e) => break Err(e),
+            }
+        };
+        // Events held back before were polled first, so these are all in order
+        self.pending_events.extend(skipped);
+        result
+    }
+
+    /// Receive all pending events from the tracker, and summarize their net effect.
+    ///
+    /// Key edits are applied to the local tracks as with
+    /// [`poll_events`](RocketClient::poll_events). This is convenient when only the latest state
+    /// matters rather than each event.
+    ///
+    /// # Errors
+    ///
+    /// This method can return an [`Error::IOError`] if Rocket tracker disconnects.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use rust_rocket::RocketClient;
+    /// # let mut rocket = RocketClient::new().unwrap();
+    /// loop {
+    ///     let state = rocket.sync().unwrap();
+    ///     if state.saved {
+    ///         rocket.save_tracks_atomic("tracks.bin").unwrap();
+    ///     }
+    ///     // Render the frame at `state.row`.
+    /// }
+    /// ```
+    pub fn sync(&mut self) -> Result<SyncState, Error> {
+        self.edited = false;
+        let mut saved = false;
+        while let Some(event) = self.poll_events()? {
+            if let Event::SaveTracks = event {
+                saved = true;
+            }
+        }
+        Ok(SyncState {
+            row: self.row,
+            paused: self.paused,
+            saved,
+            edited: self.edited,
+        })
+    }
+
+    /// Poll for new events from the tracker, processing at most `max` commands.
+    ///
+    /// This works like [`poll_events`](RocketClient::poll_events), but also returns `None` after
+    /// applying `max` key edits, even if more data is available. Call it once per frame to spread
+    /// a large dump of keys, e.g. after registering many tracks, over several frames.
+    ///
+    /// # Errors
+    ///
+    /// This method can return an [`Error::IOError`] if Rocket tracker disconnects.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use rust_rocket::RocketClient;
+    /// # let mut rocket = RocketClient::new().unwrap();
+    /// while let Some(event) = rocket.poll_events_bounded(1000).unwrap() {
+    ///     // Do something with the various events.
+    /// }
+    /// ```
+    pub fn poll_events_bounded(&mut self, max: usize) -> Result<Option<Event>, Error> {
+        let mut processed = 0;
+        while processed < max {
+            // Commands are only applied when they're complete
+            let completing = matches!(self.state, ClientState::Complete);
+            match self.poll_event()? {
+                ReceiveResult::None => return Ok(None),
+                ReceiveResult::Incomplete if completing => processed += 1,
+                ReceiveResult::Incomplete => (),
+                ReceiveResult::Some(event) => return Ok(Some(event)),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Poll for new events from the tracker, and report whether any data was received.
+    ///
+    /// This works like [`poll_events`](RocketClient::poll_events), but distinguishes between
+    /// an idle connection and one where key edits or a partial command were received.
+    /// That is useful for backing off when the tracker is idle.
+    ///
+    /// # Errors
+    ///
+    /// This method can return an [`Error::IOError`] if Rocket tracker disconnects.
+    pub fn poll_events_detailed(&mut self) -> Result<PollOutcome, Error> {
+        let mut progressing = false;
+        loop {
+            match self.poll_event()? {
+                ReceiveResult::None if progressing => return Ok(PollOutcome::Progressing),
+                ReceiveResult::None => return Ok(PollOutcome::Idle),
+                ReceiveResult::Incomplete => progressing = true,
+                ReceiveResult::Some(event) => return Ok(PollOutcome::Event(event)),
+            }
+        }
+    }
+
+    /// Poll for a single event from the tracker.
+    ///
+    /// This is the same as [`poll_events`](RocketClient::poll_events), but named for what a
+    /// single call does. As [`Event`] is `Copy` and `#[repr(C)]`, this is convenient for FFI
+    /// bindings: call it until it returns `Ok(None)`, once per frame.
+    ///
+    /// # Errors
+    ///
+    /// This method can return an [`Error::IOError`] if Rocket tracker disconnects.
+    pub fn next_event(&mut self) -> Result<Option<Event>, Error> {
+        self.poll_events()
+    }
+
+    /// Enable or disable strict mode.
+    ///
+    /// By default, protocol anomalies are tolerated: unknown commands and key edits for tracks
+    /// that weren't registered are skipped, and unknown interpolation types are read as
+    /// [`Interpolation::Step`]. Tolerated anomalies are passed to the
+    /// [warning callback](RocketClient::on_warning), or ignored if there is none. In strict mode,
+    /// they are returned as errors from [`poll_events`](RocketClient::poll_events) instead,
+    /// without applying the edit. The connection can still be used after such an error.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Register a callback for key edits received from the tracker.
+    ///
+    /// The callback is called from [`poll_events`](RocketClient::poll_events) after each edit
+    /// has been applied to the local track, so the track already has the new key.
+    /// Edits made with [`set_key`](RocketClient::set_key) or
+    /// [`delete_key`](RocketClient::delete_key) don't call it.
+    /// Registering a new callback replaces the previous one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use rust_rocket::RocketClient;
+    /// # use rust_rocket::client::EditEvent;
+    /// # let mut rocket = RocketClient::new().unwrap();
+    /// rocket.on_edit(|edit| match edit {
+    ///     EditEvent::KeySet { track, .. } | EditEvent::KeyDeleted { track, .. } => {
+    ///         println!("Track {} changed", track);
+    ///     }
+    /// });
+    /// ```
+    pub fn on_edit(&mut self, callback: impl FnMut(EditEvent) + Send + 'static) {
+        self.edit_callback = Some(Box::new(callback));
+    }
+
+    /// Register a callback for protocol anomalies tolerated outside of
+    /// [strict mode](RocketClient::set_strict).
+    ///
+    /// The callback is called from [`poll_events`](RocketClient::poll_events) with the error that
+    /// strict mode would have returned, e.g. [`Error::InvalidInterpolation`] when an unknown
+    /// interpolation type was read as [`Interpolation::Step`]. Without a callback, anomalies are
+    /// silently ignored. Registering a new callback replaces the previous one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use rust_rocket::RocketClient;
+    /// # let mut rocket = RocketClient::new().unwrap();
+    /// rocket.on_warning(|warning| eprintln!("Rocket: {}", warning));
+    /// ```
+    pub fn on_warning(&mut self, callback: impl FnMut(&Error) + Send + 'static) {
+        self.warning_callback = Some(Box::new(callback));
+    }
+
+    /// Register a callback that sees every protocol message sent to or received from the tracker.
+    ///
+    /// Each call gets one complete message, starting with its command id. This is meant for
+    /// debugging protocol issues. When no logger is set, messages aren't copied or inspected.
+    /// Setting a new logger replaces the previous one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use rust_rocket::RocketClient;
+    /// # let mut rocket = RocketClient::new().unwrap();
+    /// rocket.set_packet_logger(|direction, bytes| println!("{:?} {:02x?}", direction, bytes));
+    /// ```
+    pub fn set_packet_logger(&mut self, logger: impl FnMut(Direction, &[u8]) + Send + 'static) {
+        self.packet_logger = Some(Box::new(logger));
+    }
+
+    /// Remember the last `capacity` commands received from the tracker, for example to show
+    /// them in a debug overlay. Read them with [`command_history`](RocketClient::command_history).
+    ///
+    /// The default capacity is `0`, which remembers nothing. Lowering the capacity forgets the
+    /// oldest commands.
+    pub fn set_command_history(&mut self, capacity: usize) {
+        self.command_history.set_capacity(capacity);
+    }
+
+    /// Get the last received commands, oldest first.
+    ///
+    /// See [`set_command_history`](RocketClient::set_command_history).
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use rust_rocket::RocketClient;
+    /// let mut rocket = RocketClient::new().unwrap();
+    /// rocket.set_command_history(8);
+    /// rocket.poll_events().unwrap();
+    /// for command in rocket.command_history() {
+    ///     println!("{:?}", command);
+    /// }
+    /// ```
+    pub fn command_history(&self) -> &[LoggedCommand] {
+        self.command_history.as_slice()
+    }
+
+    /// Compute a hash of all tracks and their keys, for detecting drift from the tracker.
+    ///
+    /// The hash covers track names and indices, and each key's row, value and interpolation. It
+    /// doesn't cover local playback settings like [muting](Track::set_muted). Compare checksums
+    /// from before and after [`resync_tracks`](RocketClient::resync_tracks) and polling the
+    /// tracker's keys to find out if the local tracks had drifted. Checksums are only meant to be
+    /// compared within one version of this crate, but they are the same on all platforms.
+    pub fn checksum(&self) -> u64 {
+        // Numbers are hashed in the byte order of the file format, not the native one
+        let mut hasher = FnvHasher::default();
+        hasher.write(&(self.tracks.len() as u64).to_le_bytes());
+        for track in &self.tracks {
+            hasher.write(&(track.get_name().len() as u64).to_le_bytes());
+            hasher.write(track.get_name().as_bytes());
+            hasher.write(&(track.key_count() as u64).to_le_bytes());
+            for key in track.keys() {
+                hasher.write(&key.get_row().to_le_bytes());
+                hasher.write(&key.get_value().to_bits().to_le_bytes());
+                hasher.write(&key.get_interpolation().to_u32().to_le_bytes());
+            }
+        }
+        hasher.finish()
+    }
+
+    /// Serialize current tracks as bytes
+    /// Tracks can be turned into a [`RocketPlayer`](crate::RocketPlayer::deserialize) for playback.
+    pub fn serialize(&self) -> Vec<u8> {
+        self.serialize_filtered(|_| true)
+    }
+
+    /// Serialize current tracks as bytes, in the versioned format with `metadata`.
+    ///
+    /// The metadata can be read back with [`RocketPlayer::metadata`](crate::RocketPlayer::metadata),
+    /// e.g. to find out which build produced a file. Entries are written sorted by key, so the
+    /// same tracks and metadata always give the same bytes. See
+    /// [the file format](crate::format).
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use rust_rocket::RocketClient;
+    /// # use std::collections::HashMap;
+    /// # let rocket = RocketClient::new().unwrap();
+    /// let mut metadata = HashMap::new();
+    /// metadata.insert("build".to_owned(), "1234".to_owned());
+    /// let tracks = rocket.serialize_with_metadata(&metadata);
+    /// ```
+    pub fn serialize_with_metadata(&self, metadata: &HashMap<String, String>) -> Vec<u8> {
+        let mut wtr = vec![];
+        format::write_header(&mut wtr, metadata);
+        wtr.extend_from_slice(&self.serialize());
+        wtr
+    }
+
+    /// Take a [`Snapshot`] of the current tracks, for
+    /// [`serialize_delta`](RocketClient::serialize_delta) later.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            tracks: self.tracks.clone(),
+        }
+    }
+
+    /// Serialize the key changes since `since` as bytes, to be applied with
+    /// [`RocketPlayer::apply_delta`](crate::RocketPlayer::apply_delta).
+    ///
+    /// Only tracks with changed keys are included, identified by name, so the player's track
+    /// indices don't need to match the client's. Other track settings aren't included.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use rust_rocket::{RocketClient, RocketPlayer};
+    /// # let mut rocket = RocketClient::new().unwrap();
+    /// let mut player = RocketPlayer::deserialize(&rocket.serialize());
+    /// let mut snapshot = rocket.snapshot();
+    /// loop {
+    ///     rocket.poll_events().unwrap();
+    ///     player.apply_delta(&rocket.serialize_delta(&snapshot)).unwrap();
+    ///     snapshot = rocket.snapshot();
+    /// }
+    /// ```
+    pub fn serialize_delta(&self, since: &Snapshot) -> Vec<u8> {
+        let empty = Track::new("");
+        let changed: Vec<_> = self
+            .tracks
+            .iter()
+            .map(|track| {
+                let old = since
+                    .tracks
+                    .iter()
+                    .find(|t| t.get_name() == track.get_name())
+                    .unwrap_or(&empty);
+                (track, old.diff(track))
+            })
+            .filter(|(_, changes)| !changes.is_empty())
+            .collect();
+
+        let mut wtr = vec![];
+        format::write_delta_header(&mut wtr, changed.len());
+        for (track, changes) in changed {
+            format::write_delta_track_header(&mut wtr, track.get_name(), changes.len());
+            for change in changes {
+                match change {
+                    KeyChange::Added(key) | KeyChange::Modified { new: key, .. } => {
+                        format::write_delta_change(&mut wtr, key.get_row(), Some(&key))
                     }
-                    Err(e) => match e.kind() {
-                        std::io::ErrorKind::WouldBlock => Ok(ReceiveResult::None),
-                        _ => Err(Error::IOError(e)),
-                    },
+                    KeyChange::Removed(row) => format::write_delta_change(&mut wtr, row, None),
+                }
+            }
+        }
+        wtr
+    }
+
+    /// Serialize the tracks whose names pass `pred` as bytes.
+    ///
+    /// The result is a complete set of tracks, which can be loaded with
+    /// [`RocketPlayer::deserialize`](crate::RocketPlayer::deserialize) on its own.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use rust_rocket::RocketClient;
+    /// # let rocket = RocketClient::new().unwrap();
+    /// let scene1 = rocket.serialize_filtered(|name| name.starts_with("scene1:"));
+    /// ```
+    pub fn serialize_filtered(&self, pred: impl Fn(&str) -> bool) -> Vec<u8> {
+        let tracks: Vec<&Track> = self.tracks.iter().filter(|t| pred(t.get_name())).collect();
+        let mut wtr = vec![];
+        format::write_track_count(&mut wtr, tracks.len());
+        for t in tracks {
+            format::write_track(&mut wtr, t);
+        }
+        wtr
+    }
+
+    /// Save current tracks to a file, replacing it atomically.
+    ///
+    /// The tracks are [serialized](RocketClient::serialize) into a temporary file next to `path`,
+    /// which is then renamed to `path`. Readers of `path` see either the previous file or the
+    /// complete new one, even if the process crashes while saving.
+    ///
+    /// # Errors
+    ///
+    /// Any error from creating, writing or renaming the temporary file.
+    pub fn save_tracks_atomic<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let mut tmp_name = path.file_name().unwrap_or_default().to_owned();
+        tmp_name.push(".tmp");
+        let tmp_path = path.with_file_name(tmp_name);
+
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&self.serialize())?;
+        file.sync_all()?;
+        std::fs::rename(&tmp_path, path)
+    }
+
+    fn poll_event(&mut self) -> Result<ReceiveResult, Error> {
+        // Held back by poll_latest_row or wait_for_row
+        if let Some(event) = self.pending_events.pop_front() {
+            return Ok(ReceiveResult::Some(event));
+        }
+
+        match self.state {
+            ClientState::New => {
+                if self.receive(1)? == 0 {
+                    return Ok(ReceiveResult::None);
+                }
+                match self.cmd[0] {
+                    SET_KEY => self.state = ClientState::Incomplete(4 + 4 + 4 + 1),
+                    DELETE_KEY => self.state = ClientState::Incomplete(4 + 4),
+                    SET_ROW => self.state = ClientState::Incomplete(4),
+                    PAUSE => self.state = ClientState::Incomplete(1),
+                    SAVE_TRACKS => self.state = ClientState::Complete,
+                    _ => self.state = ClientState::Complete, // Error / Unknown
+                }
+                Ok(ReceiveResult::Incomplete)
+            }
+            ClientState::Incomplete(bytes) => {
+                let bytes_read = self.receive(bytes)?;
+                if bytes_read == 0 {
+                    return Ok(ReceiveResult::None);
                 }
+                if bytes - bytes_read > 0 {
+                    self.state = ClientState::Incomplete(bytes - bytes_read);
+                } else {
+                    self.state = ClientState::Complete;
+                }
+                Ok(ReceiveResult::Incomplete)
             }
             ClientState::Complete => {
+                if let Some(logger) = &mut self.packet_logger {
+                    logger(Direction::Received, &self.cmd);
+                }
+
                 let mut result = ReceiveResult::None;
+                let mut anomaly = None;
                 {
                     // Following reads from cmd should never fail if above match arms are correct
                     let mut cursor = Cursor::new(&self.cmd);
                     let cmd = cursor.read_u8().unwrap();
                     match cmd {
-                        0 => {
+                        SET_KEY => {
                             // usize::try_from(u32) will only be None if usize is smaller, and
                             // more than usize::MAX tracks are in use. That isn't possible because
                             // I'd imagine Vec::push and everything else will panic first.
                             // If you're running this on a microcontroller, I'd love to see it!
-                            let track = &mut self.tracks
-                                [usize::try_from(cursor.read_u32::<BigEndian>().unwrap()).unwrap()];
+                            let track =
+                                usize::try_from(cursor.read_u32::<BigEndian>().unwrap()).unwrap();
                             let row = cursor.read_u32::<BigEndian>().unwrap();
                             let value = cursor.read_f32::<BigEndian>().unwrap();
-                            let interpolation = Interpolation::from(cursor.read_u8().unwrap());
-                            let key = Key::new(row, value, interpolation);
+                            let interpolation = cursor.read_u8().unwrap();
+                            self.command_history.push(LoggedCommand::SetKey {
+                                track,
+                                row,
+                                value,
+                                interpolation,
+                            });
+                            let interpolation = Interpolation::try_from(u32::from(interpolation));
 
-                            track.set_key(key);
+                            // The tracker may be out of sync, e.g. after restarting
+                            match (self.tracks.get_mut(track), interpolation) {
+                                (None, _) => anomaly = Some(Error::UnknownTrack(track)),
+                                (Some(_), Err(e)) if self.strict => {
+                                    anomaly = Some(Error::InvalidInterpolation(e))
+                                }
+                                (Some(t), interpolation) => {
+                                    let interpolation = interpolation.unwrap_or_else(|e| {
+                                        anomaly = Some(Error::InvalidInterpolation(e));
+                                        Interpolation::Step
+                                    });
+                                    let key = Key::new(Row::from(row), value, interpolation);
+                                    t.set_key(key);
+                                    self.pending_tracks.remove(&track);
+                                    self.edited = true;
+                                    if let Some(callback) = &mut self.edit_callback {
+                                        callback(EditEvent::KeySet { track, key });
+                                    }
+                                }
+                            }
                         }
-                        1 => {
-                            let track = &mut self.tracks
-                                [usize::try_from(cursor.read_u32::<BigEndian>().unwrap()).unwrap()];
+                        DELETE_KEY => {
+                            let track =
+                                usize::try_from(cursor.read_u32::<BigEndian>().unwrap()).unwrap();
                             let row = cursor.read_u32::<BigEndian>().unwrap();
+                            self.command_history
+                                .push(LoggedCommand::DeleteKey { track, row });
 
-                            track.delete_key(row);
+                            match self.tracks.get_mut(track) {
+                                Some(t) => {
+                                    t.delete_key(Row::from(row));
+                                    self.pending_tracks.remove(&track);
+                                    self.edited = true;
+                                    if let Some(callback) = &mut self.edit_callback {
+                                        callback(EditEvent::KeyDeleted {
+                                            track,
+                                            row: Row::from(row),
+                                        });
+                                    }
+                                }
+                                None => anomaly = Some(Error::UnknownTrack(track)),
+                            }
                         }
-                        3 => {
+                        SET_ROW => {
                             let row = cursor.read_u32::<BigEndian>().unwrap();
-                            result = ReceiveResult::Some(Event::SetRow(row));
+                            self.command_history.push(LoggedCommand::SetRow(row));
+                            let row = Row::from(row);
+                            // Rows sent a while ago won't be echoed anymore
+                            while self
+                                .sent_rows
+                                .front()
+                                .is_some_and(|(_, sent)| sent.elapsed() > ECHO_TIMEOUT)
+                            {
+                                self.sent_rows.pop_front();
+                            }
+                            if self.sent_rows.front().map(|&(sent, _)| sent) == Some(row) {
+                                // Echo of a row we sent, the current row is already newer
+                                self.sent_rows.pop_front();
+                            } else {
+                                self.sent_rows.clear();
+                                self.row = row;
+                                result = ReceiveResult::Some(Event::SetRow(row));
+                            }
                         }
-                        4 => {
+                        PAUSE => {
                             let flag = cursor.read_u8().unwrap() == 1;
+                            self.command_history.push(LoggedCommand::Pause(flag));
+                            self.paused = flag;
                             result = ReceiveResult::Some(Event::Pause(flag));
                         }
-                        5 => {
+                        SAVE_TRACKS => {
+                            self.command_history.push(LoggedCommand::SaveTracks);
                             result = ReceiveResult::Some(Event::SaveTracks);
                         }
-                        _ => println!("Unknown {:?}", cmd),
+                        _ => {
+                            self.command_history.push(LoggedCommand::Unknown(cmd));
+                            anomaly = Some(Error::UnknownCommand(cmd));
+                        }
                     }
                 }
 
                 self.cmd.clear();
                 self.state = ClientState::New;
 
-                Ok(result)
+                if let Some(error) = anomaly {
+                    if self.strict {
+                        return Err(error);
+                    }
+                    if let Some(callback) = &mut self.warning_callback {
+                        callback(&error);
+                    }
+                }
+
+                Ok(match result {
+                    // A key edit was applied, keep reading
+                    ReceiveResult::None => ReceiveResult::Incomplete,
+                    result => result,
+                })
             }
         }
     }
 
+    fn send(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        if let Some(logger) = &mut self.packet_logger {
+            logger(Direction::Sent, buf);
+        }
+        self.stream.write_all(buf)
+    }
+
+    /// Send several messages with a single write.
+    fn send_all(&mut self, messages: &[Vec<u8>]) -> std::io::Result<()> {
+        if let Some(logger) = &mut self.packet_logger {
+            for message in messages {
+                logger(Direction::Sent, message);
+            }
+        }
+        self.stream.write_all(&messages.concat())
+    }
+
     fn handshake(&mut self) -> Result<(), Error> {
         let client_greeting = b"hello, synctracker!";
         let server_greeting = b"hello, demo!";
@@ -344,4 +1563,910 @@ impl RocketClient {
             Err(Error::HandshakeGreetingMismatch(buf))
         }
     }
+
+    /// Move up to `max` received bytes to the current command, reading from the tracker if none
+    /// are buffered.
+    ///
+    /// Returns how many bytes were moved, `0` if no data is available yet. The end of the stream
+    /// is an [`Error::IOError`] of kind [`std::io::ErrorKind::UnexpectedEof`].
+    fn receive(&mut self, max: usize) -> Result<usize, Error> {
+        if self.read_buffer_start == self.read_buffer_end {
+            self.read_buffer_start = 0;
+            self.read_buffer_end = 0;
+            match self.stream.read(&mut self.read_buffer) {
+                Ok(0) => return Err(Error::IOError(std::io::ErrorKind::UnexpectedEof.into())),
+                Ok(bytes_read) => self.read_buffer_end = bytes_read,
+                Err(e) => match e.kind() {
+                    std::io::ErrorKind::WouldBlock => return Ok(0),
+                    _ => return Err(Error::IOError(e)),
+                },
+            }
+        }
+        let end = self.read_buffer_end.min(self.read_buffer_start + max);
+        self.cmd
+            .extend_from_slice(&self.read_buffer[self.read_buffer_start..end]);
+        let moved = end - self.read_buffer_start;
+        self.read_buffer_start = end;
+        Ok(moved)
+    }
+
+    /// Read printable ASCII sent right after the greeting, see
+    /// [`server_info`](RocketClient::server_info).
+    ///
+    /// Waits until a command arrives or [`SERVER_INFO_TIMEOUT`] has passed, so info sent
+    /// separately from the greeting is read too. Commands start with a non-printable command id,
+    /// so they are left unread.
+    fn read_server_info(&mut self) -> Result<(), Error> {
+        use std::io::ErrorKind;
+
+        let deadline = Instant::now() + SERVER_INFO_TIMEOUT;
+        let mut info = Vec::new();
+        let mut buf = [0; 64];
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            self.stream
+                .set_read_timeout(Some(remaining))
+                .map_err(Error::Handshake)?;
+            let peeked = match self.stream.peek(&mut buf) {
+                Ok(peeked) => peeked,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => break,
+                Err(e) => return Err(Error::Handshake(e)),
+            };
+            let printable = buf[..peeked]
+                .iter()
+                .take_while(|b| matches!(b, b' '..=b'~'))
+                .count();
+            self.stream
+                .read_exact(&mut buf[..printable])
+                .map_err(Error::Handshake)?;
+            info.extend_from_slice(&buf[..printable]);
+            // A command follows, or the tracker disconnected
+            if printable < peeked || peeked == 0 {
+                break;
+            }
+        }
+        self.stream
+            .set_read_timeout(None)
+            .map_err(Error::Handshake)?;
+        // Only printable ASCII was read, so this is valid UTF-8
+        self.server_info = String::from_utf8(info).unwrap().trim().to_owned();
+        Ok(())
+    }
+}
+
+/// Convert a row to the `u32` of the Rocket protocol.
+fn wire_row(row: Row) -> Result<u32, Error> {
+    u32::try_from(row).map_err(|_| Error::RowOverflow(row))
+}
+
+/// Build a SET_KEY message.
+fn set_key_message(track_id: u32, key: &Key) -> Result<Vec<u8>, Error> {
+    let mut buf = vec![SET_KEY];
+    buf.write_u32::<BigEndian>(track_id).unwrap();
+    let row = wire_row(key.get_row())?;
+    buf.write_u32::<BigEndian>(row).unwrap();
+    buf.write_f32::<BigEndian>(key.get_value()).unwrap();
+    buf.push(key.get_interpolation().to_u8());
+    Ok(buf)
+}
+
+/// Build a DELETE_KEY message.
+fn delete_key_message(track_id: u32, row: Row) -> Result<Vec<u8>, Error> {
+    let mut buf = vec![DELETE_KEY];
+    buf.write_u32::<BigEndian>(track_id).unwrap();
+    buf.write_u32::<BigEndian>(wire_row(row)?).unwrap();
+    Ok(buf)
+}
+
+/// Build a GET_TRACK message.
+fn get_track_message(name: &str) -> Vec<u8> {
+    let mut buf = vec![GET_TRACK];
+    buf.write_u32::<BigEndian>(u32::try_from(name.len()).expect("Track name too long"))
+        .unwrap_or_else(|_|
+        // Can writes to a vec fail? Consider changing to unreachable_unchecked in 1.0
+        unreachable!());
+    buf.extend_from_slice(name.as_bytes());
+    buf
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::net::{Shutdown, TcpListener};
+
+    /// Connect a client to a fake tracker. Returns the client and the tracker's end of the
+    /// connection.
+    pub(crate) fn connect() -> (RocketClient, TcpStream) {
+        let (client, tracker) = connect_with_greeting(b"hello, demo!");
+        (client.unwrap(), tracker)
+    }
+
+    fn connect_with_greeting(
+        server_greeting: &'static [u8],
+    ) -> (Result<RocketClient, Error>, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let tracker = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut greeting = [0; 19];
+            stream.read_exact(&mut greeting).unwrap();
+            assert_eq!(&greeting, b"hello, synctracker!");
+            stream.write_all(server_greeting).unwrap();
+            stream
+        });
+        let client = RocketClient::connect(addr);
+        (client, tracker.join().unwrap())
+    }
+
+    fn read_bytes(tracker: &mut TcpStream, len: usize) -> Vec<u8> {
+        let mut buf = vec![0; len];
+        tracker.read_exact(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn reads_server_info() {
+        let (client, _tracker) = connect_with_greeting(b"hello, demo! rocket-ng 2\x04\x00");
+        let mut client = client.unwrap();
+        assert_eq!(client.server_info(), "rocket-ng 2");
+        let event = poll_until(&mut client, |client| client.poll_events().unwrap());
+        assert!(matches!(event, Event::Pause(false)));
+
+        let (client, _tracker) = connect();
+        assert_eq!(client.server_info(), "");
+
+        // Info sent separately from the greeting
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let tracker = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            read_bytes(&mut stream, 19);
+            stream.write_all(b"hello, demo!").unwrap();
+            std::thread::sleep(Duration::from_millis(10));
+            stream.write_all(b" rocket-ng 2\x04\x01").unwrap();
+            stream
+        });
+        let mut client = RocketClient::connect(addr).unwrap();
+        let _tracker = tracker.join().unwrap();
+        assert_eq!(client.server_info(), "rocket-ng 2");
+        let event = poll_until(&mut client, |client| client.poll_events().unwrap());
+        assert!(matches!(event, Event::Pause(true)));
+
+        assert!(matches!(
+            connect_with_greeting(b"hello, dome! rocket-ng 2").0,
+            Err(Error::HandshakeGreetingMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn exposes_stream() {
+        let (client, tracker) = connect();
+        assert_eq!(
+            client.get_ref().peer_addr().unwrap(),
+            tracker.local_addr().unwrap()
+        );
+        assert!(client.get_ref().take_error().unwrap().is_none());
+
+        #[cfg(unix)]
+        assert_eq!(client.as_raw_fd(), client.get_ref().as_raw_fd());
+    }
+
+    #[test]
+    fn undoes_and_redoes_edits() {
+        let (mut client, mut tracker) = connect();
+        let track = client.get_track_index_mut("test").unwrap();
+        read_bytes(&mut tracker, 1 + 4 + 4);
+
+        client.enable_history();
+        client
+            .set_key(track, Key::new(3, 1.0, Interpolation::Linear))
+            .unwrap();
+        assert_eq!(
+            read_bytes(&mut tracker, 1 + 4 + 4 + 4 + 1),
+            [SET_KEY, 0, 0, 0, 0, 0, 0, 0, 3, 0x3f, 0x80, 0, 0, 1]
+        );
+        client
+            .set_key(track, Key::new(3, 2.0, Interpolation::Step))
+            .unwrap();
+        read_bytes(&mut tracker, 1 + 4 + 4 + 4 + 1);
+
+        assert!(client.undo().unwrap());
+        read_bytes(&mut tracker, 1 + 4 + 4 + 4 + 1);
+        assert_eq!(client.get_track(track).get_value(3.), 1.0);
+
+        assert!(client.undo().unwrap());
+        assert_eq!(
+            read_bytes(&mut tracker, 1 + 4 + 4),
+            [DELETE_KEY, 0, 0, 0, 0, 0, 0, 0, 3]
+        );
+        assert!(client.get_track(track).get_key(3).is_none());
+        assert!(!client.undo().unwrap());
+
+        assert!(client.redo().unwrap());
+        read_bytes(&mut tracker, 1 + 4 + 4 + 4 + 1);
+        assert_eq!(client.get_track(track).get_value(3.), 1.0);
+        assert!(client.history().unwrap().can_redo());
+
+        // Edits that couldn't be sent stay in the history
+        client.get_ref().shutdown(Shutdown::Write).unwrap();
+        assert!(client.undo().is_err());
+        assert_eq!(client.get_track(track).get_value(3.), 1.0);
+        assert!(client.redo().is_err());
+        let history = client.history().unwrap();
+        assert!(history.can_undo() && history.can_redo());
+    }
+
+    /// Call `f` until it returns `Some`, panicking after a second.
+    fn poll_until<T>(
+        client: &mut RocketClient,
+        mut f: impl FnMut(&mut RocketClient) -> Option<T>,
+    ) -> T {
+        let start = std::time::Instant::now();
+        while start.elapsed() < std::time::Duration::from_secs(1) {
+            if let Some(result) = f(client) {
+                return result;
+            }
+        }
+        panic!("Timed out polling the client");
+    }
+
+    #[test]
+    fn calls_edit_callback() {
+        let (mut client, mut tracker) = connect();
+        let track = client.get_track_index_mut("test").unwrap();
+        read_bytes(&mut tracker, 1 + 4 + 4);
+
+        let edits = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let callback_edits = edits.clone();
+        client.on_edit(move |edit| callback_edits.lock().unwrap().push(edit));
+
+        tracker
+            .write_all(&[SET_KEY, 0, 0, 0, 0, 0, 0, 0, 3, 0x3f, 0x80, 0, 0, 1])
+            .unwrap();
+        tracker
+            .write_all(&[DELETE_KEY, 0, 0, 0, 0, 0, 0, 0, 5])
+            .unwrap();
+        poll_until(&mut client, |client| {
+            client.poll_events().unwrap();
+            (edits.lock().unwrap().len() == 2).then_some(())
+        });
+
+        let edits = edits.lock().unwrap();
+        assert!(matches!(
+            edits[0],
+            EditEvent::KeySet { track: 0, key } if key == Key::new(3, 1.0, Interpolation::Linear)
+        ));
+        assert!(matches!(
+            edits[1],
+            EditEvent::KeyDeleted { track: 0, row: 5 }
+        ));
+        assert_eq!(client.get_track(track).get_value(3.), 1.0);
+    }
+
+    #[test]
+    fn applies_key_edits_until_event() {
+        let (mut client, mut tracker) = connect();
+        client.get_track_index_mut("test").unwrap();
+
+        let mut commands = Vec::new();
+        for row in 0..3 {
+            commands.extend_from_slice(&[SET_KEY, 0, 0, 0, 0, 0, 0, 0, row, 0x3f, 0x80, 0, 0, 0]);
+        }
+        commands.extend_from_slice(&[SET_ROW, 0, 0, 0, 7]);
+        tracker.write_all(&commands).unwrap();
+        // Wait for all of it to arrive, so a single poll sees it
+        poll_until(&mut client, |client| {
+            let mut buf = [0; 64];
+            (client.get_ref().peek(&mut buf).unwrap_or(0) == commands.len()).then_some(())
+        });
+
+        assert!(matches!(client.poll_events(), Ok(Some(Event::SetRow(7)))));
+        assert_eq!(client.get_track(0).key_count(), 3);
+        assert!(matches!(client.poll_events(), Ok(None)));
+    }
+
+    #[test]
+    fn reports_poll_progress() {
+        let (mut client, mut tracker) = connect();
+        client.get_track_index_mut("test").unwrap();
+        assert!(matches!(
+            client.poll_events_detailed().unwrap(),
+            PollOutcome::Idle
+        ));
+
+        tracker.write_all(&[SET_KEY, 0, 0, 0, 0, 0, 0]).unwrap();
+        poll_until(&mut client, |client| {
+            match client.poll_events_detailed().unwrap() {
+                PollOutcome::Progressing => Some(()),
+                _ => None,
+            }
+        });
+
+        tracker
+            .write_all(&[0, 3, 0x3f, 0x80, 0, 0, 1, SET_ROW, 0, 0, 0, 7])
+            .unwrap();
+        let event = poll_until(&mut client, |client| {
+            match client.poll_events_detailed().unwrap() {
+                PollOutcome::Event(event) => Some(event),
+                _ => None,
+            }
+        });
+        assert!(matches!(event, Event::SetRow(7)));
+        assert_eq!(client.get_track(0).get_value(3.), 1.0);
+    }
+
+    #[test]
+    fn skips_unknown_tracks() {
+        let (mut client, mut tracker) = connect();
+        client.get_track_index_mut("test").unwrap();
+
+        tracker
+            .write_all(&[SET_KEY, 0, 0, 0, 1, 0, 0, 0, 3, 0x3f, 0x80, 0, 0, 1])
+            .unwrap();
+        tracker
+            .write_all(&[DELETE_KEY, 0, 0, 0, 9, 0, 0, 0, 3, SET_ROW, 0, 0, 0, 7])
+            .unwrap();
+        let event = poll_until(&mut client, |client| client.poll_events().unwrap());
+        assert!(matches!(event, Event::SetRow(7)));
+        assert_eq!(client.get_track(0).key_count(), 0);
+    }
+
+    #[test]
+    fn rejects_anomalies_in_strict_mode() {
+        let (mut client, mut tracker) = connect();
+        client.get_track_index_mut("test").unwrap();
+        client.set_strict(true);
+
+        tracker.write_all(&[9]).unwrap();
+        let error = poll_until(&mut client, |client| client.poll_events().err());
+        assert!(matches!(error, Error::UnknownCommand(9)));
+
+        tracker
+            .write_all(&[DELETE_KEY, 0, 0, 0, 1, 0, 0, 0, 3])
+            .unwrap();
+        let error = poll_until(&mut client, |client| client.poll_events().err());
+        assert!(matches!(error, Error::UnknownTrack(1)));
+
+        tracker
+            .write_all(&[SET_KEY, 0, 0, 0, 0, 0, 0, 0, 3, 0x3f, 0x80, 0, 0, 7])
+            .unwrap();
+        let error = poll_until(&mut client, |client| client.poll_events().err());
+        assert!(matches!(
+            error,
+            Error::InvalidInterpolation(InvalidInterpolation(7))
+        ));
+        assert_eq!(client.get_track(0).key_count(), 0);
+
+        // Lenient mode coerces the interpolation, and warns about it
+        client.set_strict(false);
+        let warnings = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let callback_warnings = warnings.clone();
+        client
+            .on_warning(move |warning| callback_warnings.lock().unwrap().push(warning.to_string()));
+        tracker
+            .write_all(&[SET_KEY, 0, 0, 0, 0, 0, 0, 0, 3, 0x3f, 0x80, 0, 0, 7])
+            .unwrap();
+        poll_until(&mut client, |client| {
+            client.poll_events().unwrap();
+            client.get_track(0).get_key(3).copied()
+        });
+        assert!(client.get_track(0).interpolation_at(3.) == Some(Interpolation::Step));
+        assert_eq!(
+            *warnings.lock().unwrap(),
+            ["Invalid key from the Rocket server"]
+        );
+    }
+
+    #[test]
+    fn registers_tracks() {
+        let (mut client, mut tracker) = connect();
+        client.get_track_index_mut("b").unwrap();
+        read_bytes(&mut tracker, 1 + 4 + 1);
+
+        assert_eq!(
+            client.register_tracks(&["a", "b", "c", "a"]).unwrap(),
+            [1, 0, 2, 1]
+        );
+        assert_eq!(
+            read_bytes(&mut tracker, 2 * (1 + 4 + 1)),
+            [GET_TRACK, 0, 0, 0, 1, b'a', GET_TRACK, 0, 0, 0, 1, b'c']
+        );
+        assert_eq!(client.get_track(2).get_name(), "c");
+    }
+
+    #[test]
+    fn reserves_tracks() {
+        let (client, _tracker) = connect();
+        let mut client = client.with_track_capacity(200);
+        client.get_track_index_mut("a").unwrap();
+        assert!(client.tracks.capacity() >= 200);
+    }
+
+    #[test]
+    fn commits_staged_keys() {
+        let (mut client, mut tracker) = connect();
+        client.register_tracks(&["a", "b"]).unwrap();
+        read_bytes(&mut tracker, 2 * (1 + 4 + 1));
+        client.set_key_local(1, Key::new(3, 1.0, Interpolation::Step));
+        client.set_key_local(1, Key::new(3, 0.0, Interpolation::Linear));
+        client.set_key_local(1, Key::new(4, 0.0, Interpolation::Step));
+        assert_eq!(client.get_track(1).key_count(), 2);
+
+        client.commit_track(1).unwrap();
+        client.flush().unwrap();
+        assert_eq!(
+            read_bytes(&mut tracker, 2 * (1 + 4 + 4 + 4 + 1)),
+            [
+                SET_KEY, 0, 0, 0, 1, 0, 0, 0, 3, 0, 0, 0, 0, 1, //
+                SET_KEY, 0, 0, 0, 1, 0, 0, 0, 4, 0, 0, 0, 0, 0,
+            ]
+        );
+    }
+
+    #[test]
+    fn resyncs_tracks() {
+        let (mut client, mut tracker) = connect();
+        client.register_tracks(&["a", "b"]).unwrap();
+        read_bytes(&mut tracker, 2 * (1 + 4 + 1));
+        client
+            .set_key(1, Key::new(3, 1.0, Interpolation::Step))
+            .unwrap();
+        read_bytes(&mut tracker, 1 + 4 + 4 + 4 + 1);
+
+        client.resync_tracks().unwrap();
+        assert_eq!(
+            read_bytes(&mut tracker, 2 * (1 + 4 + 1)),
+            [GET_TRACK, 0, 0, 0, 1, b'a', GET_TRACK, 0, 0, 0, 1, b'b']
+        );
+        assert_eq!(client.tracks.len(), 2);
+        assert_eq!(client.get_track(1).key_count(), 0);
+    }
+
+    #[test]
+    fn confirms_pending_tracks() {
+        let (mut client, mut tracker) = connect();
+        client.register_tracks(&["a", "b"]).unwrap();
+        assert_eq!(client.pending_tracks().collect::<Vec<_>>(), [0, 1]);
+
+        // SET_KEY on track 1, row 0, value 0.0, step
+        tracker.write_all(&[SET_KEY, 0, 0, 0, 1]).unwrap();
+        tracker.write_all(&[0; 4 + 4 + 1]).unwrap();
+        poll_until(&mut client, |client| {
+            client.poll_events().unwrap();
+            client.get_track(1).get_key(0).copied()
+        });
+        assert_eq!(client.pending_tracks().collect::<Vec<_>>(), [0]);
+
+        client.resync_tracks().unwrap();
+        assert_eq!(client.pending_tracks().collect::<Vec<_>>(), [0, 1]);
+    }
+
+    #[test]
+    fn rejects_empty_track_names() {
+        let (mut client, _tracker) = connect();
+        let error = client.get_track_index_mut("").unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(matches!(
+            client.register_tracks(&["a", ""]),
+            Err(Error::EmptyTrackName)
+        ));
+        assert!(client.tracks.is_empty());
+
+        client.get_ref().shutdown(Shutdown::Write).unwrap();
+        assert!(matches!(
+            client.register_tracks(&["a", "b"]),
+            Err(Error::IOError(_))
+        ));
+        assert!(client.tracks.is_empty());
+    }
+
+    #[test]
+    fn suppresses_row_echoes() {
+        let (mut client, mut tracker) = connect();
+        client.set_row(5).unwrap();
+        client.set_row(6).unwrap();
+        read_bytes(&mut tracker, 2 * (1 + 4));
+
+        tracker
+            .write_all(&[
+                SET_ROW, 0, 0, 0, 5, SET_ROW, 0, 0, 0, 6, SET_ROW, 0, 0, 0, 9,
+            ])
+            .unwrap();
+        let event = poll_until(&mut client, |client| client.poll_events().unwrap());
+        assert!(matches!(event, Event::SetRow(9)));
+
+        // Scrubbing to a previously sent row is not an echo anymore
+        tracker.write_all(&[SET_ROW, 0, 0, 0, 6]).unwrap();
+        let event = poll_until(&mut client, |client| client.poll_events().unwrap());
+        assert!(matches!(event, Event::SetRow(6)));
+    }
+
+    #[test]
+    fn expires_row_echoes() {
+        let (mut client, mut tracker) = connect();
+        client.set_row(5).unwrap();
+        client.set_row(7).unwrap();
+        read_bytes(&mut tracker, 2 * (1 + 4));
+
+        // A tracker that doesn't echo sends a previously sent row later
+        std::thread::sleep(ECHO_TIMEOUT + Duration::from_millis(50));
+        tracker.write_all(&[SET_ROW, 0, 0, 0, 5]).unwrap();
+        let event = poll_until(&mut client, |client| client.poll_events().unwrap());
+        assert!(matches!(event, Event::SetRow(5)));
+        assert_eq!(client.current_row(), 5);
+    }
+
+    #[test]
+    fn polls_latest_row() {
+        let (mut client, mut tracker) = connect();
+        let mut commands = vec![SET_ROW, 0, 0, 0, 1, SET_ROW, 0, 0, 0, 2, PAUSE, 1];
+        commands.extend_from_slice(&[SET_ROW, 0, 0, 0, 3, SET_ROW, 0, 0, 0, 4]);
+        tracker.write_all(&commands).unwrap();
+
+        let mut events = Vec::new();
+        poll_until(&mut client, |client| {
+            events.extend(client.poll_latest_row().unwrap());
+            (events.last().map(Event::payload) == Some(4)).then_some(())
+        });
+        // Rows may have been split over several polls, keep the last of each run
+        events.reverse();
+        events.dedup_by_key(|e| e.command_byte());
+        events.reverse();
+        assert!(matches!(
+            events[..],
+            [Event::SetRow(2), Event::Pause(true), Event::SetRow(4)]
+        ));
+    }
+
+    #[test]
+    fn parses_commands_across_reads() {
+        let (client, mut tracker) = connect();
+        // Commands are split over many reads, and reads end in the middle of commands
+        let mut client = client.with_read_buffer_size(5);
+        let track = client.get_track_index_mut("test").unwrap();
+        let mut commands = Vec::new();
+        for row in 0..100 {
+            commands.extend_from_slice(&[SET_KEY, 0, 0, 0, 0, 0, 0, 0, row, 0x3f, 0x80, 0, 0, 1]);
+        }
+        commands.extend_from_slice(&[PAUSE, 0, SET_ROW, 0, 0, 0, 7]);
+        tracker.write_all(&commands).unwrap();
+
+        let event = poll_until(&mut client, |client| client.poll_events().unwrap());
+        assert!(matches!(event, Event::Pause(false)));
+        let mut client = client.with_read_buffer_size(64);
+        let event = poll_until(&mut client, |client| client.poll_events().unwrap());
+        assert!(matches!(event, Event::SetRow(7)));
+        assert_eq!(client.get_track(track).key_count(), 100);
+    }
+
+    #[test]
+    fn waits_for_row() {
+        let (mut client, mut tracker) = connect();
+        client.get_track_index_mut("test").unwrap();
+        assert_eq!(
+            client.wait_for_row(Duration::from_millis(10)).unwrap(),
+            None
+        );
+
+        let mut commands = vec![SET_KEY, 0, 0, 0, 0, 0, 0, 0, 3, 0x3f, 0x80, 0, 0, 1];
+        commands.extend_from_slice(&[PAUSE, 0, SET_ROW, 0, 0, 0, 7]);
+        tracker.write_all(&commands).unwrap();
+        assert_eq!(
+            client.wait_for_row(Duration::from_secs(5)).unwrap(),
+            Some(7)
+        );
+        assert_eq!(client.get_track(0).get_value(3.), 1.0);
+        assert_eq!(client.tick(1).unwrap(), 8);
+        // Events other than rows are kept
+        assert!(matches!(
+            client.poll_events(),
+            Ok(Some(Event::Pause(false)))
+        ));
+
+        tracker
+            .write_all(&[SAVE_TRACKS, PAUSE, 1, SET_ROW, 0, 0, 0, 9])
+            .unwrap();
+        assert_eq!(
+            client.wait_for_row(Duration::from_secs(5)).unwrap(),
+            Some(9)
+        );
+        assert!(matches!(client.poll_events(), Ok(Some(Event::SaveTracks))));
+        assert!(matches!(client.poll_events(), Ok(Some(Event::Pause(true)))));
+    }
+
+    #[test]
+    fn syncs_state() {
+        let (mut client, mut tracker) = connect();
+        client.get_track_index_mut("test").unwrap();
+        assert_eq!(
+            client.sync().unwrap(),
+            SyncState {
+                row: 0,
+                paused: true,
+                saved: false,
+                edited: false,
+            }
+        );
+
+        tracker
+            .write_all(&[SET_ROW, 0, 0, 0, 4, PAUSE, 0, SAVE_TRACKS])
+            .unwrap();
+        tracker
+            .write_all(&[DELETE_KEY, 0, 0, 0, 0, 0, 0, 0, 5, SET_ROW, 0, 0, 0, 8])
+            .unwrap();
+        // The commands may arrive over several syncs
+        let (mut saved, mut edited) = (false, false);
+        let state = poll_until(&mut client, |client| {
+            let state = client.sync().unwrap();
+            saved |= state.saved;
+            edited |= state.edited;
+            (state.row == 8).then_some(state)
+        });
+        assert!(!state.paused);
+        assert!(saved);
+        assert!(edited);
+
+        assert!(!client.sync().unwrap().edited);
+    }
+
+    #[test]
+    fn keeps_row_rate() {
+        let (mut client, _tracker) = connect();
+        assert!(client.rows_per_second().is_none());
+        assert!(client.audio_sync(44100).is_none());
+
+        client.set_rows_per_second(8.0);
+        assert_eq!(client.rows_per_second(), Some(8.0));
+        assert_eq!(client.audio_sync(44100).unwrap().samples_to_row(44100), 8.0);
+    }
+
+    #[test]
+    fn pauses_locally() {
+        let (mut client, mut tracker) = connect();
+        assert_eq!(client.tick(1).unwrap(), 0);
+
+        client.set_paused(false);
+        assert_eq!(client.tick(2).unwrap(), 2);
+        assert_eq!(read_bytes(&mut tracker, 1 + 4), [SET_ROW, 0, 0, 0, 2]);
+
+        client.set_paused(true);
+        assert_eq!(client.tick(2).unwrap(), 2);
+        assert!(client.sync().unwrap().paused);
+    }
+
+    #[test]
+    fn seeks_rows() {
+        let (mut client, mut tracker) = connect();
+        client.seek(10).unwrap();
+        assert_eq!(read_bytes(&mut tracker, 1 + 4), [SET_ROW, 0, 0, 0, 10]);
+        assert_eq!(client.current_row(), 10);
+
+        client.set_paused(false);
+        assert_eq!(client.tick(1).unwrap(), 11);
+        assert_eq!(client.current_row(), 11);
+        client.set_row(3).unwrap();
+        assert_eq!(client.current_row(), 3);
+    }
+
+    #[test]
+    fn rejects_overflowing_rows() {
+        let (mut client, mut tracker) = connect();
+        client.get_track_index_mut("test").unwrap();
+        let row = Row::from(u32::MAX) + 1;
+        assert!(matches!(client.set_row(row), Err(Error::RowOverflow(r)) if r == row));
+        assert_eq!(client.current_row(), 0);
+        let key = Key::new(row, 1.0, Interpolation::Step);
+        assert!(matches!(client.set_key(0, key), Err(Error::RowOverflow(_))));
+        assert!(matches!(
+            client.delete_key(0, row),
+            Err(Error::RowOverflow(_))
+        ));
+        client.set_key_local(0, key);
+        assert!(matches!(client.commit_track(0), Err(Error::RowOverflow(_))));
+
+        // Nothing was sent but the track request
+        read_bytes(&mut tracker, 1 + 4 + 4);
+        client.set_row(row - 1).unwrap();
+        assert_eq!(
+            read_bytes(&mut tracker, 1 + 4),
+            [SET_ROW, 0xff, 0xff, 0xff, 0xff]
+        );
+    }
+
+    #[test]
+    fn saves_tracks_atomically() {
+        let (mut client, _tracker) = connect();
+        let track = client.get_track_index_mut("test").unwrap();
+        client
+            .set_key(track, Key::new(0, 1.0, Interpolation::Step))
+            .unwrap();
+
+        let path = std::env::temp_dir().join(format!("rocket-{}.bin", std::process::id()));
+        std::fs::write(&path, b"old").unwrap();
+        client.save_tracks_atomic(&path).unwrap();
+        let player = crate::RocketPlayer::deserialize(&std::fs::read(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(player.get_track(0).get_value(0.), 1.0);
+    }
+
+    #[test]
+    fn serializes_filtered_tracks() {
+        let (mut client, _tracker) = connect();
+        client
+            .register_tracks(&["scene1:a", "scene2:a", "scene1:b"])
+            .unwrap();
+        client
+            .set_key(2, Key::new(0, 1.0, Interpolation::Step))
+            .unwrap();
+
+        let player = crate::RocketPlayer::deserialize(
+            &client.serialize_filtered(|name| name.starts_with("scene1:")),
+        );
+        assert_eq!(player.track_count(), 2);
+        assert_eq!(player.get_track(1).get_name(), "scene1:b");
+        assert_eq!(player.get_track(1).get_value(0.), 1.0);
+    }
+
+    #[test]
+    fn remembers_commands() {
+        let (mut client, mut tracker) = connect();
+        client.set_command_history(2);
+        tracker
+            .write_all(&[DELETE_KEY, 0, 0, 0, 0, 0, 0, 0, 1])
+            .unwrap();
+        tracker.write_all(&[PAUSE, 0]).unwrap();
+        tracker.write_all(&[SET_ROW, 0, 0, 0, 5]).unwrap();
+        tracker.write_all(&[SAVE_TRACKS]).unwrap();
+        for _ in 0..2 {
+            poll_until(&mut client, |client| client.poll_events().unwrap());
+        }
+        assert_eq!(
+            client.command_history(),
+            [LoggedCommand::Pause(false), LoggedCommand::SetRow(5)]
+        );
+
+        poll_until(&mut client, |client| client.poll_events().unwrap());
+        assert_eq!(
+            client.command_history(),
+            [LoggedCommand::SetRow(5), LoggedCommand::SaveTracks]
+        );
+        client.set_command_history(1);
+        assert_eq!(client.command_history(), [LoggedCommand::SaveTracks]);
+    }
+
+    #[test]
+    fn serializes_metadata() {
+        let (mut client, _tracker) = connect();
+        client.get_track_index_mut("a").unwrap();
+        let mut metadata = HashMap::new();
+        metadata.insert("build".to_owned(), "1234".to_owned());
+        metadata.insert("created".to_owned(), "2024-01-01".to_owned());
+
+        let player = crate::RocketPlayer::deserialize(&client.serialize_with_metadata(&metadata));
+        assert_eq!(*player.metadata(), metadata);
+        assert_eq!(player.get_track(0).get_name(), "a");
+        assert!(crate::RocketPlayer::deserialize(&client.serialize())
+            .metadata()
+            .is_empty());
+    }
+
+    #[test]
+    fn checksums_tracks() {
+        let (mut client, _tracker) = connect();
+        client.register_tracks(&["a", "b"]).unwrap();
+        let empty = client.checksum();
+        client
+            .set_key(1, Key::new(0, 1.0, Interpolation::Step))
+            .unwrap();
+        let edited = client.checksum();
+        assert_ne!(edited, empty);
+        // The same on big-endian platforms
+        assert_eq!(edited, 0xff40_1d00_577c_f68a);
+
+        client
+            .set_key(1, Key::new(0, 1.0, Interpolation::Linear))
+            .unwrap();
+        assert_ne!(client.checksum(), edited);
+        client.delete_key(1, 0).unwrap();
+        assert_eq!(client.checksum(), empty);
+    }
+
+    #[test]
+    fn serializes_deltas() {
+        let (mut client, _tracker) = connect();
+        client.register_tracks(&["a", "b"]).unwrap();
+        client
+            .set_key(0, Key::new(0, 1.0, Interpolation::Step))
+            .unwrap();
+        client
+            .set_key(0, Key::new(5, 2.0, Interpolation::Step))
+            .unwrap();
+        let mut player = crate::RocketPlayer::deserialize(&client.serialize());
+        let snapshot = client.snapshot();
+        assert_eq!(
+            client.serialize_delta(&snapshot),
+            b"RKTDLTA\x01\0\0\0\0\0\0\0\0"
+        );
+
+        client
+            .set_key(0, Key::new(0, 3.0, Interpolation::Linear))
+            .unwrap();
+        client.delete_key(0, 5).unwrap();
+        client.get_track_index_mut("c").unwrap();
+        client
+            .set_key(2, Key::new(1, 4.0, Interpolation::Step))
+            .unwrap();
+        player
+            .apply_delta(&client.serialize_delta(&snapshot))
+            .unwrap();
+
+        assert_eq!(player.track_count(), 3);
+        assert!(player.get_track(0).keys() == client.get_track(0).keys());
+        assert!(player.get_track(2).keys() == client.get_track(2).keys());
+        assert_eq!(player.get_track(2).get_name(), "c");
+
+        let full = crate::RocketPlayer::deserialize(&client.serialize());
+        let mut empty = crate::RocketPlayer::new(Vec::new());
+        empty
+            .apply_delta(&client.serialize_delta(&Snapshot::default()))
+            .unwrap();
+        assert_eq!(empty.track_count(), 2);
+        assert!(empty.get_track(0).keys() == full.get_track(0).keys());
+    }
+
+    #[test]
+    fn bounds_processed_commands() {
+        let (mut client, mut tracker) = connect();
+        client.set_command_history(8);
+        let mut commands = Vec::new();
+        for _ in 0..3 {
+            commands.extend_from_slice(&[DELETE_KEY, 0, 0, 0, 0, 0, 0, 0, 0]);
+        }
+        commands.extend_from_slice(&[PAUSE, 0]);
+        tracker.write_all(&commands).unwrap();
+        // Wait for all of it to arrive, so only the bound stops polling
+        poll_until(&mut client, |client| {
+            let mut buf = [0; 64];
+            (client.get_ref().peek(&mut buf).unwrap_or(0) == commands.len()).then_some(())
+        });
+
+        assert!(client.poll_events_bounded(2).unwrap().is_none());
+        assert_eq!(client.command_history().len(), 2);
+        assert!(matches!(
+            client.poll_events_bounded(2),
+            Ok(Some(Event::Pause(false)))
+        ));
+        assert_eq!(client.command_history().len(), 4);
+        assert!(client.poll_events_bounded(2).unwrap().is_none());
+        assert_eq!(client.command_history().len(), 4);
+    }
+
+    #[test]
+    fn logs_packets() {
+        let (mut client, mut tracker) = connect();
+        let packets = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let logger_packets = packets.clone();
+        client.set_packet_logger(move |direction, bytes| {
+            logger_packets
+                .lock()
+                .unwrap()
+                .push((direction, bytes.to_vec()))
+        });
+
+        client.set_row(1).unwrap();
+        tracker.write_all(&[PAUSE, 0]).unwrap();
+        poll_until(&mut client, |client| client.poll_events().unwrap());
+
+        assert_eq!(
+            *packets.lock().unwrap(),
+            [
+                (Direction::Sent, vec![SET_ROW, 0, 0, 0, 1]),
+                (Direction::Received, vec![PAUSE, 0]),
+            ]
+        );
+    }
 }
@@ -0,0 +1,505 @@
+//! This module contains the specification of the track file format, and functions for reading
+//! and writing it.
+//!
+//! [`RocketClient::serialize`](crate::RocketClient::serialize) and
+//! [`RocketPlayer::try_deserialize`](crate::RocketPlayer::try_deserialize) are built on these
+//! functions. They're public so that other tools can read and write the format without
+//! reimplementing it.
+//!
+//! # Specification
+//!
+//! All numbers are little-endian, see [`ByteOrder`]. Note that the tracker protocol is
+//! big-endian instead.
+//!
+//! The original format is just the tracks: a `u64` track count, then for each track a `u64` name
+//! length, the UTF-8 name and a `u64` key count, then for each key a `u32` row, an `f32` value and
+//! a `u32` interpolation. The highest bit of the interpolation, [`HOLD_FLAG`], marks
+//! [held](Key::with_hold) keys. Track names are unique and not empty.
+//!
+//! The versioned format starts with [`MAGIC`]: the 7 bytes `RKTTRKS` and a version byte, currently
+//! [`VERSION`]. It's followed by a metadata section: a `u64` length in bytes, then a `u64` entry
+//! count and each key and value as a `u64` length and a UTF-8 string. Readers skip anything after
+//! the entries up to the end of the section, so it can be extended. The tracks follow in the
+//! original format. Readers accept both formats.
+//!
+//! A delta of key changes starts with [`DELTA_MAGIC`]: the 7 bytes `RKTDLTA` and a version byte,
+//! currently [`DELTA_VERSION`]. It's followed by a `u64` count of changed tracks, and for each
+//! track a `u64` name length, the UTF-8 name and a `u64` change count. Each change is a `u8` type
+//! and a `u32` row: [`DELTA_SET_KEY`] is followed by an `f32` value and a `u32` interpolation like
+//! in a track, and [`DELTA_DELETE_KEY`] deletes the key at the row.
+//!
+//! # Examples
+//!
+//! ```
+//! # use rust_rocket::format;
+//! # use rust_rocket::interpolation::Interpolation;
+//! # use rust_rocket::track::{Key, Track};
+//! # use std::{collections::HashMap, io::Cursor};
+//! let track = Track::from_keys("camera:x", vec![Key::new(4, 1.0, Interpolation::Linear)]);
+//! let mut data = Vec::new();
+//! format::write_header(&mut data, &HashMap::new());
+//! format::write_track_count(&mut data, 1);
+//! format::write_track(&mut data, &track);
+//!
+//! let mut bytes = Cursor::new(&data[..]);
+//! assert!(format::read_header(&mut bytes).unwrap().is_empty());
+//! assert_eq!(format::read_track_count(&mut bytes).unwrap(), 1);
+//! assert_eq!(format::read_track_header(&mut bytes, 0).unwrap(), ("camera:x", 1));
+//! assert_eq!(format::read_key(&mut bytes).unwrap().get_row(), 4);
+//! ```
+use crate::interpolation::Interpolation;
+use crate::player::DeserializeError;
+use crate::track::{Key, Row, Track};
+
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use std::{collections::HashMap, convert::TryFrom, io::Cursor};
+
+/// The byte order of all numbers in the format, for use with the `byteorder` crate.
+pub type ByteOrder = LE;
+
+/// Identifies the versioned format. The last byte is the version.
+pub const MAGIC: &[u8; 8] = b"RKTTRKS\x01";
+/// The version of the versioned format that's written and read.
+pub const VERSION: u8 = MAGIC[7];
+
+/// Identifies deltas. The last byte is the version.
+pub const DELTA_MAGIC: &[u8; 8] = b"RKTDLTA\x01";
+/// The version of the delta format that's written and read.
+pub const DELTA_VERSION: u8 = DELTA_MAGIC[7];
+/// Delta change type that sets a key.
+pub const DELTA_SET_KEY: u8 = 0;
+/// Delta change type that deletes a key.
+pub const DELTA_DELETE_KEY: u8 = 1;
+
+/// Bit of a serialized interpolation that marks a [held](Key::with_hold) key.
+pub const HOLD_FLAG: u32 = 1 << 31;
+
+/// Size of a serialized key in bytes: row, value and interpolation.
+pub const KEY_SIZE: usize = 4 + 4 + 4;
+/// Smallest possible serialized track: name length and key count, without name or keys.
+const TRACK_HEADER_SIZE: u64 = 8 + 8;
+/// Smallest possible delta change: type and row.
+const CHANGE_SIZE: u64 = 1 + 4;
+
+/// Write the header of the versioned format with `metadata`.
+///
+/// Entries are written sorted by key, so the same metadata always gives the same bytes.
+pub fn write_header(wtr: &mut Vec<u8>, metadata: &HashMap<String, String>) {
+    let mut entries: Vec<_> = metadata.iter().collect();
+    entries.sort();
+    let mut section = vec![];
+    section.write_u64::<LE>(entries.len() as u64).unwrap();
+    for string in entries.into_iter().flat_map(|(k, v)| [k, v]) {
+        write_string(&mut section, string);
+    }
+
+    wtr.extend_from_slice(MAGIC);
+    wtr.write_u64::<LE>(section.len() as u64).unwrap();
+    wtr.extend_from_slice(&section);
+}
+
+/// Write the number of tracks that follow.
+pub fn write_track_count(wtr: &mut Vec<u8>, count: usize) {
+    wtr.write_u64::<LE>(count as u64).unwrap();
+}
+
+/// Write a track's name and keys.
+///
+/// # Panics
+///
+/// Will panic if a key's row exceeds [`u32::MAX`].
+pub fn write_track(wtr: &mut Vec<u8>, track: &Track) {
+    write_string(wtr, track.get_name());
+    wtr.write_u64::<LE>(track.key_count() as u64).unwrap();
+    for key in track.keys() {
+        write_key(wtr, key);
+    }
+}
+
+/// Write a key of a track.
+///
+/// # Panics
+///
+/// Will panic if the key's row exceeds [`u32::MAX`].
+pub fn write_key(wtr: &mut Vec<u8>, key: &Key) {
+    write_row(wtr, key.get_row());
+    write_key_value(wtr, key);
+}
+
+/// Write the header of a delta, with the number of changed tracks that follow.
+pub fn write_delta_header(wtr: &mut Vec<u8>, track_count: usize) {
+    wtr.extend_from_slice(DELTA_MAGIC);
+    wtr.write_u64::<LE>(track_count as u64).unwrap();
+}
+
+/// Write the name of a changed track in a delta, and the number of changes that follow.
+pub fn write_delta_track_header(wtr: &mut Vec<u8>, name: &str, change_count: usize) {
+    write_string(wtr, name);
+    wtr.write_u64::<LE>(change_count as u64).unwrap();
+}
+
+/// Write a change of a delta: setting `key`, or deleting the key at `row` if `key` is `None`.
+///
+/// # Panics
+///
+/// Will panic if `key` is not at `row`, or if `row` exceeds [`u32::MAX`].
+pub fn write_delta_change(wtr: &mut Vec<u8>, row: Row, key: Option<&Key>) {
+    match key {
+        Some(key) => {
+            assert_eq!(key.get_row(), row, "Delta key is not at the changed row");
+            wtr.push(DELTA_SET_KEY);
+            write_row(wtr, row);
+            write_key_value(wtr, key);
+        }
+        None => {
+            wtr.push(DELTA_DELETE_KEY);
+            write_row(wtr, row);
+        }
+    }
+}
+
+/// Write a row as a `u32`.
+fn write_row(wtr: &mut Vec<u8>, row: Row) {
+    let row = u32::try_from(row).expect("Row too large for the track format");
+    wtr.write_u32::<LE>(row).unwrap();
+}
+
+/// Write the value and interpolation of a key.
+fn write_key_value(wtr: &mut Vec<u8>, key: &Key) {
+    let hold = if key.is_held() { HOLD_FLAG } else { 0 };
+    wtr.write_f32::<LE>(key.get_value()).unwrap();
+    wtr.write_u32::<LE>(key.get_interpolation().to_u32() | hold)
+        .unwrap();
+}
+
+/// Write a `u64` length and a UTF-8 string.
+fn write_string(wtr: &mut Vec<u8>, string: &str) {
+    wtr.write_u64::<LE>(string.len() as u64).unwrap();
+    wtr.extend_from_slice(string.as_bytes());
+}
+
+/// Read the header of the versioned format, and return its metadata.
+///
+/// If the data at the current position doesn't start with the header, it's in the original
+/// format. Nothing is read in that case, and the metadata is empty.
+///
+/// # Errors
+///
+/// [`DeserializeError::UnsupportedVersion`] if the header has an unknown version, or any error
+/// from reading the metadata section.
+pub fn read_header(bytes: &mut Cursor<&[u8]>) -> Result<HashMap<String, String>, DeserializeError> {
+    read_header_within(bytes, data_end(bytes))
+}
+
+/// Like [`read_header`], but checks lengths against the offset `end` instead of the end of the
+/// data, for data that is still arriving. Truncated data is always an
+/// [`DeserializeError::UnexpectedEof`].
+pub(crate) fn read_header_within(
+    bytes: &mut Cursor<&[u8]>,
+    end: u64,
+) -> Result<HashMap<String, String>, DeserializeError> {
+    if !remaining(bytes).starts_with(&MAGIC[..MAGIC.len() - 1]) {
+        return Ok(HashMap::new());
+    }
+    bytes.set_position(bytes.position() + MAGIC.len() as u64 - 1);
+    let version = read(bytes, "format version", ReadBytesExt::read_u8)?;
+    if version != VERSION {
+        return Err(DeserializeError::UnsupportedVersion { version });
+    }
+    read_metadata(bytes, end)
+}
+
+/// Read the number of tracks that follow.
+///
+/// # Errors
+///
+/// Any error from reading the count, including counts that can't fit in the rest of the data.
+pub fn read_track_count(bytes: &mut Cursor<&[u8]>) -> Result<usize, DeserializeError> {
+    read_track_count_within(bytes, data_end(bytes))
+}
+
+/// Like [`read_track_count`], but checks the count against the offset `end`, see
+/// [`read_header_within`].
+pub(crate) fn read_track_count_within(
+    bytes: &mut Cursor<&[u8]>,
+    end: u64,
+) -> Result<usize, DeserializeError> {
+    read_length(bytes, "track count", TRACK_HEADER_SIZE, end)
+}
+
+/// Read the name and key count of the track with index `track_index`. The keys follow.
+///
+/// # Errors
+///
+/// Any error from reading the name or key count, including empty or invalid names.
+pub fn read_track_header<'a>(
+    bytes: &mut Cursor<&'a [u8]>,
+    track_index: usize,
+) -> Result<(&'a str, usize), DeserializeError> {
+    read_track_header_within(bytes, track_index, data_end(bytes))
+}
+
+/// Like [`read_track_header`], but checks lengths against the offset `end`, see
+/// [`read_header_within`].
+pub(crate) fn read_track_header_within<'a>(
+    bytes: &mut Cursor<&'a [u8]>,
+    track_index: usize,
+    end: u64,
+) -> Result<(&'a str, usize), DeserializeError> {
+    let name = read_name(bytes, track_index, end)?;
+    let key_count = read_length(bytes, "key count", KEY_SIZE as u64, end)?;
+    Ok((name, key_count))
+}
+
+/// Read a key of a track.
+///
+/// # Errors
+///
+/// Any error from reading the key, including unknown interpolation types.
+pub fn read_key(bytes: &mut Cursor<&[u8]>) -> Result<Key, DeserializeError> {
+    let row = read(bytes, "key row", ReadBytesExt::read_u32::<LE>)?;
+    read_key_value(bytes, Row::from(row))
+}
+
+/// Read the header of a delta, and return the number of changed tracks that follow.
+///
+/// # Errors
+///
+/// [`DeserializeError::InvalidDelta`] if the data at the current position is not a delta,
+/// [`DeserializeError::UnsupportedVersion`] if it has an unknown version, or any error from
+/// reading the track count.
+pub fn read_delta_header(bytes: &mut Cursor<&[u8]>) -> Result<usize, DeserializeError> {
+    if !remaining(bytes).starts_with(&DELTA_MAGIC[..DELTA_MAGIC.len() - 1]) {
+        return Err(DeserializeError::InvalidDelta {
+            offset: bytes.position(),
+        });
+    }
+    bytes.set_position(bytes.position() + DELTA_MAGIC.len() as u64 - 1);
+    let version = read(bytes, "format version", ReadBytesExt::read_u8)?;
+    if version != DELTA_VERSION {
+        return Err(DeserializeError::UnsupportedVersion { version });
+    }
+    read_length(bytes, "track count", TRACK_HEADER_SIZE, data_end(bytes))
+}
+
+/// Read the name of the changed track with index `track_index` in a delta, and the number of
+/// changes that follow.
+///
+/// # Errors
+///
+/// Any error from reading the name or change count, including empty or invalid names.
+pub fn read_delta_track_header<'a>(
+    bytes: &mut Cursor<&'a [u8]>,
+    track_index: usize,
+) -> Result<(&'a str, usize), DeserializeError> {
+    let end = data_end(bytes);
+    let name = read_name(bytes, track_index, end)?;
+    let change_count = read_length(bytes, "change count", CHANGE_SIZE, end)?;
+    Ok((name, change_count))
+}
+
+/// Read a change of a delta: the changed row, and the new key or `None` if the key was deleted.
+///
+/// # Errors
+///
+/// [`DeserializeError::InvalidDelta`] if the change has an unknown type, or any error from
+/// reading the key.
+pub fn read_delta_change(
+    bytes: &mut Cursor<&[u8]>,
+) -> Result<(Row, Option<Key>), DeserializeError> {
+    let offset = bytes.position();
+    let kind = read(bytes, "change type", ReadBytesExt::read_u8)?;
+    let row = Row::from(read(bytes, "key row", ReadBytesExt::read_u32::<LE>)?);
+    match kind {
+        DELTA_SET_KEY => Ok((row, Some(read_key_value(bytes, row)?))),
+        DELTA_DELETE_KEY => Ok((row, None)),
+        _ => Err(DeserializeError::InvalidDelta { offset }),
+    }
+}
+
+/// Get the data after the current position.
+fn remaining<'a>(bytes: &Cursor<&'a [u8]>) -> &'a [u8] {
+    let data = *bytes.get_ref();
+    &data[(bytes.position() as usize).min(data.len())..]
+}
+
+/// Get the offset of the end of the data.
+fn data_end(bytes: &Cursor<&[u8]>) -> u64 {
+    bytes.get_ref().len() as u64
+}
+
+/// Read `len` bytes, reporting where `field` started if the data is truncated.
+fn take<'a>(
+    bytes: &mut Cursor<&'a [u8]>,
+    field: &'static str,
+    len: usize,
+) -> Result<&'a [u8], DeserializeError> {
+    let offset = bytes.position();
+    match remaining(bytes).get(..len) {
+        Some(taken) => {
+            bytes.set_position(offset + len as u64);
+            Ok(taken)
+        }
+        None => Err(DeserializeError::UnexpectedEof {
+            offset,
+            field,
+            source: std::io::ErrorKind::UnexpectedEof.into(),
+        }),
+    }
+}
+
+/// Read a value with `read`, reporting where `field` ended if the data is truncated.
+fn read<'a, T>(
+    bytes: &mut Cursor<&'a [u8]>,
+    field: &'static str,
+    read: impl FnOnce(&mut Cursor<&'a [u8]>) -> std::io::Result<T>,
+) -> Result<T, DeserializeError> {
+    let offset = bytes.position();
+    read(bytes).map_err(|source| DeserializeError::UnexpectedEof {
+        offset,
+        field,
+        source,
+    })
+}
+
+/// Read a `u64` length and a track name of that length.
+fn read_name<'a>(
+    bytes: &mut Cursor<&'a [u8]>,
+    track_index: usize,
+    end: u64,
+) -> Result<&'a str, DeserializeError> {
+    let name_len = read_length(bytes, "track name length", 1, end)?;
+    let offset = bytes.position();
+    if name_len == 0 {
+        return Err(DeserializeError::EmptyName {
+            offset,
+            track_index,
+        });
+    }
+    let name = take(bytes, "track name", name_len)?;
+    std::str::from_utf8(name).map_err(|source| DeserializeError::InvalidName {
+        offset,
+        track_index,
+        source,
+    })
+}
+
+/// Read the value and interpolation of the key at `row`.
+fn read_key_value(bytes: &mut Cursor<&[u8]>, row: Row) -> Result<Key, DeserializeError> {
+    let value = read(bytes, "key value", ReadBytesExt::read_f32::<LE>)?;
+    let offset = bytes.position();
+    let raw = read(bytes, "key interpolation", ReadBytesExt::read_u32::<LE>)?;
+    let interp = Interpolation::try_from(raw & !HOLD_FLAG)
+        .map_err(|source| DeserializeError::InvalidInterpolation { offset, source })?;
+    Ok(Key::new(row, value, interp).with_hold(raw & HOLD_FLAG != 0))
+}
+
+/// Read the metadata section, leaving `bytes` at the end of it.
+fn read_metadata(
+    bytes: &mut Cursor<&[u8]>,
+    end: u64,
+) -> Result<HashMap<String, String>, DeserializeError> {
+    let section_len = read_length(bytes, "metadata length", 1, end)?;
+    let start = bytes.position();
+    take(bytes, "metadata", section_len)?;
+    // Entries are read from the section only, so lengths are checked against its end
+    let section_end = bytes.position();
+    let mut section = Cursor::new(&bytes.get_ref()[..section_end as usize]);
+    section.set_position(start);
+
+    let entry_count = read_length(&mut section, "metadata entry count", 8 + 8, section_end)?;
+    let mut metadata = HashMap::with_capacity(entry_count);
+    for _ in 0..entry_count {
+        let key = read_string(&mut section, "metadata key length")?;
+        let value = read_string(&mut section, "metadata value length")?;
+        metadata.insert(key.to_owned(), value.to_owned());
+    }
+    Ok(metadata)
+}
+
+/// Read a `u64` length and a UTF-8 metadata string of that length.
+fn read_string<'a>(
+    bytes: &mut Cursor<&'a [u8]>,
+    field: &'static str,
+) -> Result<&'a str, DeserializeError> {
+    let len = read_length(bytes, field, 1, data_end(bytes))?;
+    let offset = bytes.position();
+    let string = take(bytes, field, len)?;
+    std::str::from_utf8(string)
+        .map_err(|source| DeserializeError::InvalidMetadata { offset, source })
+}
+
+/// Read a `u64` length of items that are at least `item_size` bytes each, and check that that
+/// many items could fit before the offset `end`, usually the end of the data.
+fn read_length(
+    bytes: &mut Cursor<&[u8]>,
+    field: &'static str,
+    item_size: u64,
+    end: u64,
+) -> Result<usize, DeserializeError> {
+    let offset = bytes.position();
+    let len = read(bytes, field, ReadBytesExt::read_u64::<LE>)?;
+    let remaining = end.saturating_sub(bytes.position());
+    match len.checked_mul(item_size) {
+        Some(size) if size <= remaining => Ok(len as usize),
+        _ => Err(DeserializeError::LengthOutOfBounds {
+            offset,
+            field,
+            len,
+            remaining,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_deltas() {
+        let key = Key::new(3, 1.5, Interpolation::Ramp).with_hold(true);
+        let mut data = Vec::new();
+        write_delta_header(&mut data, 1);
+        write_delta_track_header(&mut data, "test", 2);
+        write_delta_change(&mut data, 3, Some(&key));
+        write_delta_change(&mut data, 5, None);
+
+        let mut bytes = Cursor::new(&data[..]);
+        assert_eq!(read_delta_header(&mut bytes).unwrap(), 1);
+        assert_eq!(read_delta_track_header(&mut bytes, 0).unwrap(), ("test", 2));
+        assert!(read_delta_change(&mut bytes).unwrap() == (3, Some(key)));
+        assert!(read_delta_change(&mut bytes).unwrap() == (5, None));
+        assert_eq!(bytes.position() as usize, data.len());
+    }
+
+    #[test]
+    fn reads_both_formats() {
+        let mut metadata = HashMap::new();
+        metadata.insert("build".to_owned(), "1234".to_owned());
+        let mut track = Track::new("test");
+        track.set_key(Key::new(2, 1.0, Interpolation::Smooth));
+
+        let mut data = Vec::new();
+        write_header(&mut data, &metadata);
+        let tracks_start = data.len();
+        write_track_count(&mut data, 1);
+        write_track(&mut data, &track);
+        assert_eq!(data.len() - tracks_start, 8 + 8 + 4 + 8 + KEY_SIZE);
+
+        let mut bytes = Cursor::new(&data[..]);
+        assert_eq!(read_header(&mut bytes).unwrap(), metadata);
+        assert_eq!(bytes.position() as usize, tracks_start);
+
+        // The original format has no header
+        let mut bytes = Cursor::new(&data[tracks_start..]);
+        assert!(read_header(&mut bytes).unwrap().is_empty());
+        assert_eq!(read_track_count(&mut bytes).unwrap(), 1);
+        assert_eq!(read_track_header(&mut bytes, 0).unwrap(), ("test", 1));
+        assert!(read_key(&mut bytes).unwrap() == Key::new(2, 1.0, Interpolation::Smooth));
+
+        assert!(matches!(
+            read_delta_header(&mut Cursor::new(&data[..])),
+            Err(DeserializeError::InvalidDelta { offset: 0 })
+        ));
+    }
+}
@@ -0,0 +1,45 @@
+//! This module contains a fast hasher for looking up tracks by name.
+//!
+//! Track names are short and come from the demo itself, so hash flooding isn't a concern.
+//! [`FnvHasher`] gives up the DoS resistance of the standard library's SipHash for speed on
+//! short keys.
+use std::hash::{BuildHasherDefault, Hasher};
+
+/// A `HashMap` using [`FnvHasher`].
+pub(crate) type FnvHashMap<K, V> = std::collections::HashMap<K, V, BuildHasherDefault<FnvHasher>>;
+
+const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const PRIME: u64 = 0x0100_0000_01b3;
+
+/// A 64-bit FNV-1a hasher.
+pub(crate) struct FnvHasher(u64);
+
+impl Default for FnvHasher {
+    fn default() -> Self {
+        Self(OFFSET_BASIS)
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(PRIME);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_fnv1a() {
+        let mut hasher = FnvHasher::default();
+        hasher.write(b"a");
+        assert_eq!(hasher.finish(), 0xaf63_dc4c_8601_ec8c);
+    }
+}
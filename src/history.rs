@@ -0,0 +1,87 @@
+//! This module contains the [`EditHistory`] type used for undoing key edits.
+use crate::track::{Key, Row};
+
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+/// A change of the key at a row of a track.
+pub(crate) struct Edit {
+    pub track: usize,
+    pub row: Row,
+    pub old: Option<Key>,
+    pub new: Option<Key>,
+}
+
+impl Edit {
+    fn inverse(self) -> Edit {
+        Edit {
+            old: self.new,
+            new: self.old,
+            ..self
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+/// The `EditHistory` Type. This records key edits made by a [`RocketClient`](crate::RocketClient).
+///
+/// Enable it with [`RocketClient::enable_history`](crate::RocketClient::enable_history).
+pub struct EditHistory {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+}
+
+impl EditHistory {
+    /// Construct a new, empty `EditHistory`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check if there are edits to undo.
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    /// Check if there are undone edits to redo.
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Forget all edits.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    /// Record a new edit. This forgets any undone edits.
+    pub(crate) fn record(&mut self, edit: Edit) {
+        self.undo.push(edit);
+        self.redo.clear();
+    }
+
+    /// Get the edit that reverts the latest edit, without moving it to the undone edits.
+    pub(crate) fn next_undo(&self) -> Option<Edit> {
+        self.undo.last().map(|edit| edit.inverse())
+    }
+
+    /// Get the latest undone edit, without moving it back to the edits.
+    pub(crate) fn next_redo(&self) -> Option<Edit> {
+        self.redo.last().copied()
+    }
+
+    /// Move the latest edit to the undone edits, after its [`next_undo`](EditHistory::next_undo)
+    /// has been applied.
+    pub(crate) fn undone(&mut self) {
+        if let Some(edit) = self.undo.pop() {
+            self.redo.push(edit);
+        }
+    }
+
+    /// Move the latest undone edit back to the edits, after its
+    /// [`next_redo`](EditHistory::next_redo) has been applied.
+    pub(crate) fn redone(&mut self) {
+        if let Some(edit) = self.redo.pop() {
+            self.undo.push(edit);
+        }
+    }
+}
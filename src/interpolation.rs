@@ -1,7 +1,41 @@
 //! This module contains anything related to interpolation.
+use std::convert::TryFrom;
+use thiserror::Error;
 
+/// The `Ease` Trait. This maps the position `t` between two keys, from `0` to `1`, to how far the
+/// value has moved from the first key to the second.
+///
+/// [`Interpolation`] implements it for the standard Rocket modes. Custom eases can be used for
+/// local playback with [`Track::set_custom_ease`](crate::track::Track::set_custom_ease), but
+/// can't be sent to the tracker or serialized. Closures taking and returning an `f32` are eases
+/// too.
+///
+/// # Examples
+///
+/// ```
+/// # use rust_rocket::interpolation::{Ease, Interpolation};
+/// let bounce = |t: f32| 1.0 - (1.0 - t).powi(2);
+/// assert_eq!(bounce.ease(0.5), 0.75);
+/// assert_eq!(Interpolation::Linear.ease(0.5), 0.5);
+/// ```
+pub trait Ease {
+    /// Perform the easing.
+    fn ease(&self, t: f32) -> f32;
+}
+
+impl<F: Fn(f32) -> f32> Ease for F {
+    fn ease(&self, t: f32) -> f32 {
+        self(t)
+    }
+}
+
+impl Ease for Interpolation {
+    fn ease(&self, t: f32) -> f32 {
+        self.interpolate(t)
+    }
+}
 
-#[derive( Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "debug", derive(Debug))]
 /// The `Interpolation` Type.
 /// This represents the various forms of interpolation that can be performed.
@@ -12,23 +46,85 @@ pub enum Interpolation {
     Linear = 1,
     /// `t * t * (3 - 2 * t)`
     Smooth = 2,
-    /// `t.powi(2)`
+    /// `t * t`
     Ramp = 3,
 }
 
-impl From<u8> for Interpolation {
-    fn from(raw: u8) -> Interpolation {
+#[derive(Debug, Error)]
+#[error("Invalid interpolation type {0}")]
+/// The `InvalidInterpolation` Type. Returned when a number doesn't map to an [`Interpolation`].
+pub struct InvalidInterpolation(pub u32);
+
+impl TryFrom<u32> for Interpolation {
+    type Error = InvalidInterpolation;
+
+    fn try_from(raw: u32) -> Result<Interpolation, InvalidInterpolation> {
         match raw {
-            0 => Interpolation::Step,
-            1 => Interpolation::Linear,
-            2 => Interpolation::Smooth,
-            3 => Interpolation::Ramp,
-            _ => Interpolation::Step,
+            0 => Ok(Interpolation::Step),
+            1 => Ok(Interpolation::Linear),
+            2 => Ok(Interpolation::Smooth),
+            3 => Ok(Interpolation::Ramp),
+            _ => Err(InvalidInterpolation(raw)),
         }
     }
 }
 
+/// Unknown values are interpreted as [`Interpolation::Step`].
+impl From<u8> for Interpolation {
+    fn from(raw: u8) -> Interpolation {
+        Interpolation::try_from(u32::from(raw)).unwrap_or(Interpolation::Step)
+    }
+}
+
+const ALL: [Interpolation; 4] = [
+    Interpolation::Step,
+    Interpolation::Linear,
+    Interpolation::Smooth,
+    Interpolation::Ramp,
+];
+
 impl Interpolation {
+    /// The number of `Interpolation` variants.
+    pub const COUNT: usize = ALL.len();
+
+    /// Get all `Interpolation` variants, ordered by their numeric value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::interpolation::Interpolation;
+    /// assert_eq!(Interpolation::all().len(), Interpolation::COUNT);
+    /// for (i, interpolation) in Interpolation::all().iter().enumerate() {
+    ///     assert_eq!(*interpolation as usize, i);
+    /// }
+    /// ```
+    pub fn all() -> &'static [Interpolation] {
+        &ALL
+    }
+
+    /// Get the numeric value of the interpolation, as sent over the Rocket protocol.
+    ///
+    /// This is the inverse of `Interpolation::from(u8)` for valid values.
+    pub const fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// Get the numeric value of the interpolation, as stored in serialized tracks.
+    ///
+    /// This is the inverse of `Interpolation::try_from(u32)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::interpolation::Interpolation;
+    /// # use std::convert::TryFrom;
+    /// let raw = Interpolation::Smooth.to_u32();
+    /// assert!(Interpolation::try_from(raw).unwrap() == Interpolation::Smooth);
+    /// ```
+    pub const fn to_u32(self) -> u32 {
+        self as u32
+    }
+
     /// This performs the interpolation.
     ///
     /// # Examples
@@ -42,13 +138,95 @@ impl Interpolation {
     /// # use rust_rocket::interpolation::Interpolation;
     /// assert_eq!(Interpolation::Step.interpolate(0.5), 0.);
     /// ```
-    pub fn interpolate(&self, t: f32) -> f32 {
-        // println!("interp {t} {self:?}");
+    ///
+    /// This is a `const fn`, so it can be used to build tables at compile time.
+    ///
+    /// ```
+    /// # use rust_rocket::interpolation::Interpolation;
+    /// const HALFWAY: f32 = Interpolation::Smooth.interpolate(0.5);
+    /// assert_eq!(HALFWAY, 0.5);
+    /// ```
+    pub const fn interpolate(&self, t: f32) -> f32 {
         match *self {
             Interpolation::Step => 0.0,
             Interpolation::Linear => t,
             Interpolation::Smooth => t * t * (3.0 - 2.0 * t),
-            Interpolation::Ramp => t.powi(2),
+            Interpolation::Ramp => t * t,
+        }
+    }
+
+    /// This performs the interpolation in `f64`, with the same formulas as
+    /// [`interpolate`](Interpolation::interpolate).
+    ///
+    /// This avoids the banding `f32` causes in long, slow segments when baking offline.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::interpolation::Interpolation;
+    /// assert_eq!(Interpolation::Ramp.interpolate_f64(0.5), 0.25);
+    /// ```
+    pub const fn interpolate_f64(&self, t: f64) -> f64 {
+        match *self {
+            Interpolation::Step => 0.0,
+            Interpolation::Linear => t,
+            Interpolation::Smooth => t * t * (3.0 - 2.0 * t),
+            Interpolation::Ramp => t * t,
+        }
+    }
+
+    /// Sample the interpolation at `steps` evenly spaced points from `t = 0` to `t = 1`.
+    ///
+    /// Interpolation is defined for `t` in `[0, 1]`, which is the range between two keys.
+    /// A single step samples `t = 0`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::interpolation::Interpolation;
+    /// assert_eq!(Interpolation::Ramp.sample(3), [0., 0.25, 1.]);
+    /// ```
+    pub fn sample(&self, steps: usize) -> Vec<f32> {
+        let last = steps.saturating_sub(1).max(1) as f32;
+        (0..steps)
+            .map(|i| self.interpolate(i as f32 / last))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ramp_matches_powi() {
+        for i in 0..=1000 {
+            let t = i as f32 / 1000.;
+            assert_eq!(
+                Interpolation::Ramp.interpolate(t).to_bits(),
+                t.powi(2).to_bits()
+            );
+        }
+    }
+
+    #[test]
+    fn converts_both_ways() {
+        for &interpolation in Interpolation::all() {
+            assert!(Interpolation::from(interpolation.to_u8()) == interpolation);
+            assert!(Interpolation::try_from(interpolation.to_u32()).unwrap() == interpolation);
+        }
+        assert!(Interpolation::try_from(Interpolation::COUNT as u32).is_err());
+    }
+
+    #[test]
+    fn f64_matches_f32() {
+        for interpolation in Interpolation::all() {
+            for i in 0..=1000 {
+                let t = i as f32 / 1000.;
+                let difference = f64::from(interpolation.interpolate(t))
+                    - interpolation.interpolate_f64(f64::from(t));
+                assert!(difference.abs() < 1e-6);
+            }
         }
     }
 }
@@ -2,10 +2,23 @@
 //! It is designed to work as a client library for GNU Rocket.
 
 
+pub mod audio;
+#[cfg(feature = "client")]
+pub mod bridge;
 #[cfg(feature = "client")]
 pub mod client;
+pub mod format;
+mod hash;
+#[cfg(feature = "client")]
+pub mod history;
 pub mod interpolation;
+#[cfg(feature = "client")]
+pub mod offline;
 pub mod player;
+#[cfg(feature = "client")]
+pub mod session;
+#[cfg(feature = "client")]
+pub mod threaded;
 pub mod track;
 
 
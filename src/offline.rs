@@ -0,0 +1,213 @@
+//! This module contains [`OfflineClient`], a stand-in for [`RocketClient`] that works without a
+//! tracker.
+//!
+//! It's meant for unit testing tools built on [`RocketClient`]: edits update local tracks just
+//! like with a real client, but instead of being sent anywhere, they're recorded as
+//! [`Operation`]s that tests can inspect. Events are scripted with
+//! [`push_event`](OfflineClient::push_event).
+//!
+//! # Examples
+//!
+//! ```
+//! # use rust_rocket::client::Event;
+//! # use rust_rocket::interpolation::Interpolation;
+//! # use rust_rocket::offline::{OfflineClient, Operation};
+//! # use rust_rocket::track::{Key, TrackEditor};
+//! let mut rocket = OfflineClient::new();
+//! rocket.push_event(Event::SetRow(4));
+//!
+//! // The tool being tested
+//! let track = rocket.get_track_index_mut("camera:x").unwrap();
+//! while let Some(Event::SetRow(row)) = rocket.poll_events().unwrap() {
+//!     rocket.set_key(track, Key::new(row, 1.0, Interpolation::Linear)).unwrap();
+//! }
+//!
+//! assert!(rocket.operations()[1] == Operation::SetKey {
+//!     track,
+//!     key: Key::new(4, 1.0, Interpolation::Linear),
+//! });
+//! ```
+#[cfg(doc)]
+use crate::client::RocketClient;
+use crate::client::{Error, Event};
+use crate::track::{Key, Row, Track, TrackEditor, TrackSource};
+
+use std::collections::VecDeque;
+
+#[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+/// The `Operation` Type. These are the messages an [`OfflineClient`] would have sent to a tracker.
+pub enum Operation {
+    /// A new track was requested.
+    GetTrack(String),
+    /// A key was inserted or updated on the track with this index.
+    SetKey { track: usize, key: Key },
+    /// The key at `row` was deleted from the track with this index.
+    DeleteKey { track: usize, row: Row },
+    /// The row was changed.
+    SetRow(Row),
+}
+
+#[derive(Default)]
+/// The `OfflineClient` Type. This has the track editing API of [`RocketClient`], but records
+/// operations instead of sending them.
+///
+/// None of the methods fail. They return [`Result`]s so that code written for [`RocketClient`]
+/// works unchanged.
+pub struct OfflineClient {
+    tracks: Vec<Track>,
+    operations: Vec<Operation>,
+    events: VecDeque<Event>,
+}
+
+impl TrackSource for OfflineClient {
+    fn get_track_index(&self, name: &str) -> Option<usize> {
+        self.tracks.iter().position(|t| t.get_name() == name)
+    }
+
+    fn get_track(&self, index: usize) -> &Track {
+        &self.tracks[index]
+    }
+}
+
+impl TrackEditor for OfflineClient {
+    /// Get track by name, creating it if it doesn't exist yet.
+    ///
+    /// # Errors
+    ///
+    /// An error of kind [`std::io::ErrorKind::InvalidInput`] if `name` is empty, like
+    /// [`RocketClient`].
+    fn get_track_index_mut(&mut self, name: &str) -> Result<usize, std::io::Error> {
+        if let Some(index) = self.get_track_index(name) {
+            Ok(index)
+        } else if name.is_empty() {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                Error::EmptyTrackName,
+            ))
+        } else {
+            self.operations.push(Operation::GetTrack(name.to_owned()));
+            self.tracks.push(Track::new(name));
+            Ok(self.tracks.len() - 1)
+        }
+    }
+
+    fn set_key(&mut self, track: usize, key: Key) -> Result<(), std::io::Error> {
+        OfflineClient::set_key(self, track, key).map_err(std::io::Error::other)
+    }
+}
+
+impl OfflineClient {
+    /// Construct a new `OfflineClient` without tracks or events.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get multiple tracks by name, creating the ones that don't exist yet.
+    ///
+    /// See [`RocketClient::register_tracks`].
+    ///
+    /// # Errors
+    ///
+    /// [`Error::EmptyTrackName`] if a name is empty. No tracks are registered in that case.
+    pub fn register_tracks(&mut self, names: &[&str]) -> Result<Vec<usize>, Error> {
+        if names.iter().any(|name| name.is_empty()) {
+            return Err(Error::EmptyTrackName);
+        }
+        Ok(names
+            .iter()
+            .map(|name| self.get_track_index_mut(name).unwrap())
+            .collect())
+    }
+
+    /// Record a row change.
+    pub fn set_row(&mut self, row: Row) -> Result<(), Error> {
+        self.operations.push(Operation::SetRow(row));
+        Ok(())
+    }
+
+    /// Insert or update a key on a track, and record it.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `track` is not a valid track index.
+    pub fn set_key(&mut self, track: usize, key: Key) -> Result<(), Error> {
+        self.tracks[track].set_key(key);
+        self.operations.push(Operation::SetKey { track, key });
+        Ok(())
+    }
+
+    /// Delete the key at `row` from a track, and record it.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `track` is not a valid track index.
+    pub fn delete_key(&mut self, track: usize, row: Row) -> Result<(), Error> {
+        self.tracks[track].delete_key(row);
+        self.operations.push(Operation::DeleteKey { track, row });
+        Ok(())
+    }
+
+    /// Queue an event to be returned by [`poll_events`](OfflineClient::poll_events).
+    pub fn push_event(&mut self, event: Event) {
+        self.events.push_back(event);
+    }
+
+    /// Get the next queued event, or `None` if all have been polled.
+    pub fn poll_events(&mut self) -> Result<Option<Event>, Error> {
+        Ok(self.events.pop_front())
+    }
+
+    /// Get the recorded operations, oldest first.
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    /// Take the recorded operations, leaving none.
+    pub fn take_operations(&mut self) -> Vec<Operation> {
+        std::mem::take(&mut self.operations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpolation::Interpolation;
+
+    #[test]
+    fn records_operations() {
+        let mut rocket = OfflineClient::new();
+        assert_eq!(rocket.register_tracks(&["a", "b", "a"]).unwrap(), [0, 1, 0]);
+        let key = Key::new(2, 1.0, Interpolation::Step);
+        TrackEditor::set_key(&mut rocket, 1, key).unwrap();
+        rocket.delete_key(1, 2).unwrap();
+        rocket.set_row(3).unwrap();
+
+        assert!(
+            rocket.take_operations()
+                == [
+                    Operation::GetTrack("a".to_owned()),
+                    Operation::GetTrack("b".to_owned()),
+                    Operation::SetKey { track: 1, key },
+                    Operation::DeleteKey { track: 1, row: 2 },
+                    Operation::SetRow(3),
+                ]
+        );
+        assert!(rocket.operations().is_empty());
+        assert_eq!(rocket.get_track(1).key_count(), 0);
+    }
+
+    #[test]
+    fn returns_scripted_events() {
+        let mut rocket = OfflineClient::new();
+        rocket.push_event(Event::Pause(false));
+        rocket.push_event(Event::SetRow(1));
+
+        assert!(matches!(
+            rocket.poll_events(),
+            Ok(Some(Event::Pause(false)))
+        ));
+        assert!(matches!(rocket.poll_events(), Ok(Some(Event::SetRow(1)))));
+        assert!(matches!(rocket.poll_events(), Ok(None)));
+    }
+}
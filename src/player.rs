@@ -1,24 +1,122 @@
 //! This module contains a barebones player.
-use std::io::Cursor;
+use std::{
+    borrow::Cow,
+    collections::{hash_map::Entry, BTreeMap, HashMap},
+    io::Cursor,
+};
 
-use byteorder::{ReadBytesExt, LE};
+use thiserror::Error;
 
 use crate::{
-    interpolation::Interpolation,
-    track::{Key, RocketEngine, Track},
+    format,
+    hash::FnvHashMap,
+    interpolation::{Interpolation, InvalidInterpolation},
+    track::{Key, Row, Track, TrackSource},
 };
 
+/// The most bytes a set of tracks decoded by [`PlayerDecoder`] may have by default.
+const DEFAULT_MAX_DECODER_SIZE: u64 = 64 << 20;
+
+#[derive(Debug, Error)]
+/// The `DeserializeError` Type. Returned by [`RocketPlayer::try_deserialize`].
+///
+/// Each variant has the byte `offset` of the value that couldn't be read, and most name the
+/// `field` being read, such as `"track name length"` or `"key value"`.
+pub enum DeserializeError {
+    #[error("Track data ended at byte {offset} while reading the {field}")]
+    /// The data ended in the middle of a value.
+    UnexpectedEof {
+        offset: u64,
+        field: &'static str,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error(
+        "The {field} {len} at byte {offset} doesn't fit in the remaining {remaining} bytes of \
+         track data"
+    )]
+    /// A track count, name length or key count is larger than the remaining data could hold.
+    LengthOutOfBounds {
+        offset: u64,
+        field: &'static str,
+        len: u64,
+        remaining: u64,
+    },
+    #[error("The name of track {track_index} at byte {offset} is not valid UTF-8")]
+    /// A track name is not valid UTF-8.
+    InvalidName {
+        offset: u64,
+        track_index: usize,
+        #[source]
+        source: std::str::Utf8Error,
+    },
+    #[error("The name of track {track_index} at byte {offset} is empty")]
+    /// A track name is empty.
+    EmptyName { offset: u64, track_index: usize },
+    #[error("The name of track {track_index} at byte {offset} is already used")]
+    /// Two tracks have the same name.
+    DuplicateName {
+        offset: u64,
+        track_index: usize,
+        #[source]
+        source: DuplicateTrackError,
+    },
+    #[error("Invalid delta data at byte {offset}")]
+    /// Data passed to [`RocketPlayer::apply_delta`] is not a delta, or has an unknown type of
+    /// change.
+    InvalidDelta { offset: u64 },
+    #[error("Unsupported track data format version {version}")]
+    /// The data has the [versioned format](RocketPlayer#file-format) or is a
+    /// [delta](RocketPlayer::apply_delta), but has a version this crate can't read.
+    UnsupportedVersion { version: u8 },
+    #[error("A metadata string at byte {offset} is not valid UTF-8")]
+    /// A metadata key or value is not valid UTF-8.
+    InvalidMetadata {
+        offset: u64,
+        #[source]
+        source: std::str::Utf8Error,
+    },
+    #[error("Invalid key interpolation at byte {offset} of track data")]
+    /// A key has an unknown interpolation type.
+    InvalidInterpolation {
+        offset: u64,
+        #[source]
+        source: InvalidInterpolation,
+    },
+}
+
+#[derive(Debug, Error)]
+#[error("A track named {0:?} already exists")]
+/// The `DuplicateTrackError` Type. Returned when tracks with the same name are combined.
+pub struct DuplicateTrackError(pub String);
+
+/// Values of all tracks at a row, by track name. See [`RocketPlayer::snapshot`].
+pub type ValueSnapshot = HashMap<String, f32>;
+
 /// A player for tracks dumped by
-/// [`RocketClient::save_tracks`](crate::RocketClient::save_tracks).
+/// [`RocketClient::serialize`](crate::RocketClient::serialize) or
+/// [`RocketClient::save_tracks_atomic`](crate::RocketClient::save_tracks_atomic).
+///
+/// # File format
+///
+/// Tracks are read in the original format, or in the versioned format written by
+/// [`RocketClient::serialize_with_metadata`](crate::RocketClient::serialize_with_metadata).
+/// Both are specified in [the format module](crate::format), which also has functions for reading
+/// and writing them.
+///
+/// Looking up tracks by name with [`get_track_index`](TrackSource::get_track_index) uses a hash
+/// map with the FNV-1a hash. It's much faster than the standard library's SipHash for short
+/// track names, but doesn't resist hash flooding. That's irrelevant for track names, which come
+/// from the demo itself.
 ///
 /// # Examples
 ///
 /// ```rust,no_run
 /// # use rust_rocket::RocketPlayer;
-/// # use crate::rust_rocket::track::RocketEngine;
+/// # use crate::rust_rocket::track::TrackSource;
 /// // let client = RocketClient::new().unwrap();
 /// // ...
-/// // Run the demo and edit your sync tracks, then call save_tracks
+/// // Run the demo and edit your sync tracks, then call save_tracks_atomic
 /// // ...
 /// // let tracks = client.serialize();
 /// // ...
@@ -30,95 +128,690 @@ use crate::{
 /// ```
 pub struct RocketPlayer {
     tracks: Vec<Track>,
+    indices: FnvHashMap<Cow<'static, str>, usize>,
+    metadata: HashMap<String, String>,
 }
 
-impl RocketEngine for RocketPlayer {
+impl TrackSource for RocketPlayer {
     fn get_track_index(&self, name: &str) -> Option<usize> {
-        self.tracks
-            .iter()
-            .enumerate()
-            .find(|t| t.1.get_name() == name)
-            .map(|t| t.0)
-    }
-    fn get_track_index_mut(&mut self, name: &str) -> Result<usize, std::io::Error> {
-        Ok(self.get_track_index(name).unwrap())
+        self.indices.get(name).copied()
     }
     fn get_track(&self, index: usize) -> &Track {
         &self.tracks[index]
     }
+
+    /// Get the value of a track at `row`, honoring solo like
+    /// [`RocketPlayer::get_value`].
+    fn sample(&self, index: usize, row: f32) -> f32 {
+        self.get_value(index, row)
+    }
 }
 
 impl RocketPlayer {
     /// Constructs a `RocketPlayer` from `Track`s.
+    ///
+    /// Tracks are given indices in order, and track names must be unique. Keys need no
+    /// normalization, because a [`Track`] always keeps its keys sorted by row with at most one key
+    /// per row.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if several tracks have the same name.
+    /// Use [`try_new`](RocketPlayer::try_new) to handle that.
     pub fn new(tracks: Vec<Track>) -> Self {
-        // Convert to a HashMap for perf (not benchmarked)
-        Self { tracks: tracks }
+        Self::try_new(tracks).expect("Duplicate track names")
+    }
+
+    /// Constructs a `RocketPlayer` from `Track`s, like [`new`](RocketPlayer::new).
+    ///
+    /// # Errors
+    ///
+    /// [`DuplicateTrackError`] if several tracks have the same name.
+    pub fn try_new(tracks: Vec<Track>) -> Result<Self, DuplicateTrackError> {
+        let mut player = Self::with_capacity(tracks.len());
+        for track in tracks {
+            player.push(track)?;
+        }
+        Ok(player)
+    }
+
+    fn with_capacity(tracks: usize) -> Self {
+        Self {
+            tracks: Vec::with_capacity(tracks),
+            indices: FnvHashMap::with_capacity_and_hasher(tracks, Default::default()),
+            metadata: HashMap::new(),
+        }
+    }
+
+    /// Add a track with the next index, if its name is not used yet.
+    fn push(&mut self, track: Track) -> Result<(), DuplicateTrackError> {
+        match self.indices.entry(track.get_name_cow().clone()) {
+            Entry::Occupied(entry) => Err(DuplicateTrackError(entry.key().to_string())),
+            Entry::Vacant(entry) => {
+                entry.insert(self.tracks.len());
+                self.tracks.push(track);
+                Ok(())
+            }
+        }
     }
 
     pub fn track_count(&self) -> usize {
         self.tracks.len()
     }
 
+    /// Count the keys of all tracks using each interpolation, like
+    /// [`Track::interpolation_counts`].
+    pub fn interpolation_counts(&self) -> [usize; Interpolation::COUNT] {
+        let mut counts = [0; Interpolation::COUNT];
+        for track in &self.tracks {
+            for (count, track_count) in counts.iter_mut().zip(track.interpolation_counts()) {
+                *count += track_count;
+            }
+        }
+        counts
+    }
+
+    /// Get the metadata stored with the tracks, such as a build id.
+    ///
+    /// This is empty unless the tracks were deserialized from the
+    /// [versioned format](RocketPlayer#file-format).
+    pub fn metadata(&self) -> &HashMap<String, String> {
+        &self.metadata
+    }
+
+    /// Get every key of every track, with the name of its track.
+    ///
+    /// Keys are ordered by track index, then by row.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::RocketPlayer;
+    /// # use rust_rocket::interpolation::Interpolation;
+    /// # use rust_rocket::track::{Key, Track};
+    /// let track = Track::from_keys("test", vec![Key::new(4, 1.0, Interpolation::Step)]);
+    /// let player = RocketPlayer::new(vec![track]);
+    /// let keys = player.all_keys();
+    /// assert_eq!(keys[0].0, "test");
+    /// assert_eq!(keys[0].1.get_row(), 4);
+    /// ```
+    pub fn all_keys(&self) -> Vec<(&str, &Key)> {
+        self.tracks
+            .iter()
+            .flat_map(|track| track.keys().iter().map(move |key| (track.get_name(), key)))
+            .collect()
+    }
+
+    /// Get the value of a track at `row`, honoring solo.
+    ///
+    /// While any track is [soloed](Track::set_soloed), tracks that aren't soloed return their
+    /// [default](Track::set_default), like [muted](Track::set_muted) tracks. Otherwise this is
+    /// the same as [`Track::get_value`].
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `index` is not a valid track index.
+    pub fn get_value(&self, index: usize, row: f32) -> f32 {
+        solo_value(&self.tracks[index], self.is_soloing(), row)
+    }
+
+    /// Get the name and value of every track at `row`, ordered by index, honoring solo like
+    /// [`get_value`](RocketPlayer::get_value).
+    ///
+    /// Values are computed lazily and names are borrowed from the player, so this doesn't
+    /// allocate, unlike [`snapshot`](RocketPlayer::snapshot).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::RocketPlayer;
+    /// # use rust_rocket::track::Track;
+    /// let player = RocketPlayer::new(vec![Track::new("a"), Track::new("b")]);
+    /// for (name, value) in player.values_at(10.) {
+    ///     println!("{},{}", name, value);
+    /// }
+    /// ```
+    pub fn values_at(&self, row: f32) -> impl Iterator<Item = (&str, f32)> + '_ {
+        let soloing = self.is_soloing();
+        self.tracks
+            .iter()
+            .map(move |t| (t.get_name(), solo_value(t, soloing, row)))
+    }
+
+    /// Check if any track is soloed.
+    fn is_soloing(&self) -> bool {
+        self.tracks.iter().any(Track::is_soloed)
+    }
+
+    /// Get the values of all tracks at `row`, honoring solo like
+    /// [`get_value`](RocketPlayer::get_value).
+    ///
+    /// The snapshot owns its data, so it can be passed around without borrowing the player.
+    pub fn snapshot(&self, row: f32) -> ValueSnapshot {
+        self.values_at(row)
+            .map(|(name, value)| (name.to_owned(), value))
+            .collect()
+    }
+
+    /// Get the value of a track at `row`, wrapped to a loop of `loop_len` rows.
+    ///
+    /// The track is evaluated at `row` modulo `loop_len`, so rows `0..loop_len` repeat forever.
+    /// Nothing is interpolated across the loop seam: just before the seam the value is that of
+    /// the track just before `loop_len`, and at the seam it jumps to the value at row 0.
+    /// Put a key at `loop_len` with the same value as at row 0 for a seamless loop.
+    ///
+    /// A `loop_len` of `0` disables looping. Solo is honored like in
+    /// [`get_value`](RocketPlayer::get_value).
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `index` is not a valid track index.
+    pub fn looped_value(&self, index: usize, row: f32, loop_len: Row) -> f32 {
+        let row = match loop_len {
+            0 => row,
+            _ => row.rem_euclid(loop_len as f32),
+        };
+        self.get_value(index, row)
+    }
+
+    /// Get the row of the last key in any track, or `0` if there are no keys.
+    pub fn duration(&self) -> Row {
+        self.tracks
+            .iter()
+            .filter_map(Track::length)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Move all tracks from `other` into this player.
+    ///
+    /// Tracks from `other` keep their order and are given indices after the existing tracks.
+    ///
+    /// # Errors
+    ///
+    /// [`DuplicateTrackError`] if a track in `other` has the same name as a track in this player.
+    /// No tracks are moved in that case.
+    pub fn merge(&mut self, other: RocketPlayer) -> Result<(), DuplicateTrackError> {
+        if let Some(track) = other
+            .tracks
+            .iter()
+            .find(|t| self.get_track_index(t.get_name()).is_some())
+        {
+            return Err(DuplicateTrackError(track.get_name().to_owned()));
+        }
+        for track in other.tracks {
+            self.push(track)?;
+        }
+        Ok(())
+    }
+
+    /// Deserialize tracks produced by [`RocketClient::serialize`](crate::RocketClient::serialize).
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `data` is not valid track data.
+    /// Use [`try_deserialize`](RocketPlayer::try_deserialize) to handle untrusted input.
     pub fn deserialize(data: &[u8]) -> Self {
+        Self::try_deserialize(data).expect("Invalid track data")
+    }
+
+    /// Deserialize tracks produced by [`RocketClient::serialize`](crate::RocketClient::serialize).
+    ///
+    /// Every length read from `data` is validated against the remaining bytes before anything is
+    /// sliced or allocated, so corrupt input results in an error instead of a panic or a huge
+    /// allocation.
+    ///
+    /// `data` is only borrowed while parsing, so it can be a read-only memory map of a track file,
+    /// avoiding reading the whole file into memory first. Only track names and keys are copied.
+    ///
+    /// ```rust,ignore
+    /// let file = std::fs::File::open("tracks.bin")?;
+    /// // Safety: the file must not be modified while it's mapped
+    /// let mmap = unsafe { memmap2::Mmap::map(&file)? };
+    /// let player = RocketPlayer::try_deserialize(&mmap)?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// - [`DeserializeError::UnexpectedEof`] if `data` is truncated.
+    /// - [`DeserializeError::UnsupportedVersion`] if `data` has an unknown format version.
+    /// - [`DeserializeError::InvalidMetadata`] if a metadata string is not valid UTF-8.
+    /// - [`DeserializeError::LengthOutOfBounds`] if a track count, name length or key count
+    ///   doesn't fit in the remaining data.
+    /// - [`DeserializeError::InvalidName`] if a track name is not valid UTF-8.
+    /// - [`DeserializeError::EmptyName`] if a track name is empty.
+    /// - [`DeserializeError::DuplicateName`] if several tracks have the same name.
+    /// - [`DeserializeError::InvalidInterpolation`] if a key's interpolation type is unknown.
+    pub fn try_deserialize(data: &[u8]) -> Result<Self, DeserializeError> {
+        Self::parse(data, |name| Cow::Owned(name.to_owned()))
+    }
+
+    /// Deserialize tracks produced by [`RocketClient::serialize`](crate::RocketClient::serialize)
+    /// from `'static` data, such as an [`include_bytes!`] in a release build.
+    ///
+    /// This works like [`try_deserialize`](RocketPlayer::try_deserialize), but track names are
+    /// borrowed from `data` instead of being copied, saving an allocation per track.
+    ///
+    /// This only serves data embedded in the binary. A memory map is not `'static`, so load those
+    /// with [`try_deserialize`](RocketPlayer::try_deserialize), which copies the names.
+    ///
+    /// ```rust,ignore
+    /// static TRACKS: &[u8] = include_bytes!("tracks.bin");
+    /// let player = RocketPlayer::try_deserialize_static(TRACKS)?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The same as [`try_deserialize`](RocketPlayer::try_deserialize).
+    pub fn try_deserialize_static(data: &'static [u8]) -> Result<Self, DeserializeError> {
+        Self::parse(data, Cow::Borrowed)
+    }
+
+    fn parse<'a>(
+        data: &'a [u8],
+        make_name: fn(&'a str) -> Cow<'static, str>,
+    ) -> Result<Self, DeserializeError> {
         let mut bytes = Cursor::new(data);
-        // println!("{:?}", bytes);
-        let track_count = bytes.read_u64::<LE>().unwrap();
-        // println!("track count {track_count}");
-        let mut tracks = Vec::with_capacity(track_count as usize);
-        for _i in 0..track_count {
-            let name_len = bytes.read_u64::<LE>().unwrap() as usize;
-            let name = std::str::from_utf8(
-                &bytes.get_ref()[bytes.position() as usize..bytes.position() as usize + name_len],
-            )
-            .unwrap();
-            bytes.set_position(bytes.position() + name_len as u64);
-
-            let key_count = bytes.read_u64::<LE>().unwrap() as usize;
-            let mut t = Track::with_capacity(name, key_count as usize);
-            for _k in 0..key_count {
-                let row = bytes.read_u32::<LE>().unwrap();
-                let value = bytes.read_f32::<LE>().unwrap();
-                let interp: Interpolation = match bytes.read_u32::<LE>().unwrap() {
-                    0 => Interpolation::Step,
-                    1 => Interpolation::Linear,
-                    2 => Interpolation::Smooth,
-                    3 => Interpolation::Ramp,
-                    _ => unreachable!(),
-                };
-                let key = Key::new(row, value, interp);
-                t.set_key(key);
+        let metadata = format::read_header(&mut bytes)?;
+
+        let track_count = format::read_track_count(&mut bytes)?;
+        let mut player = Self::with_capacity(track_count);
+        player.metadata = metadata;
+        for track_index in 0..track_count {
+            player.read_track(&mut bytes, track_index, make_name)?;
+        }
+        Ok(player)
+    }
+
+    /// Read a track and add it with the next index.
+    fn read_track<'a>(
+        &mut self,
+        bytes: &mut Cursor<&'a [u8]>,
+        track_index: usize,
+        make_name: fn(&'a str) -> Cow<'static, str>,
+    ) -> Result<(), DeserializeError> {
+        // The name follows its `u64` length
+        let offset = bytes.position() + 8;
+        let (name, key_count) = format::read_track_header(bytes, track_index)?;
+        let mut t = Track::with_name(make_name(name), key_count);
+        for _k in 0..key_count {
+            t.set_key(format::read_key(bytes)?);
+        }
+
+        self.push(t)
+            .map_err(|source| DeserializeError::DuplicateName {
+                offset,
+                track_index,
+                source,
+            })
+    }
+
+    /// Apply changes produced by
+    /// [`RocketClient::serialize_delta`](crate::RocketClient::serialize_delta).
+    ///
+    /// Tracks are matched by name, and tracks that don't exist yet are added with the next
+    /// indices. Nothing is changed if the delta can't be read.
+    ///
+    /// See [the format module](crate::format) for the delta format.
+    ///
+    /// # Errors
+    ///
+    /// - [`DeserializeError::InvalidDelta`] if `delta` is not a delta, or a change has an unknown
+    ///   type.
+    /// - [`DeserializeError::UnsupportedVersion`] if `delta` has an unknown format version.
+    /// - The same errors as [`try_deserialize`](RocketPlayer::try_deserialize) for invalid track
+    ///   names and keys.
+    pub fn apply_delta(&mut self, delta: &[u8]) -> Result<(), DeserializeError> {
+        let mut bytes = Cursor::new(delta);
+        // Read everything before applying, so invalid deltas don't change anything
+        let track_count = format::read_delta_header(&mut bytes)?;
+        let mut tracks = Vec::with_capacity(track_count);
+        for track_index in 0..track_count {
+            let (name, change_count) = format::read_delta_track_header(&mut bytes, track_index)?;
+            let changes = (0..change_count)
+                .map(|_| format::read_delta_change(&mut bytes))
+                .collect::<Result<Vec<_>, _>>()?;
+            tracks.push((name, changes));
+        }
+
+        for (name, changes) in tracks {
+            let index = match self.get_track_index(name) {
+                Some(index) => index,
+                None => {
+                    self.push(Track::new(name))
+                        .expect("Track names are checked above");
+                    self.tracks.len() - 1
+                }
+            };
+            let track = &mut self.tracks[index];
+            for (row, key) in changes {
+                match key {
+                    Some(key) => track.set_key(key),
+                    None => track.delete_key(row),
+                }
             }
+        }
+        Ok(())
+    }
+}
+
+/// The `OrderedPlayer` Type. This is a player that keeps tracks sorted by name, for tools that
+/// add, remove and list tracks.
+///
+/// Unlike [`RocketPlayer`], tracks don't have indices, so it doesn't implement [`TrackSource`].
+/// Tracks are looked up by name in `O(log n)` time, and iterated in name order.
+///
+/// # Examples
+///
+/// ```
+/// # use rust_rocket::player::OrderedPlayer;
+/// # use rust_rocket::track::Track;
+/// let mut player = OrderedPlayer::new();
+/// player.insert(Track::new("scene:b"));
+/// player.insert(Track::new("scene:a"));
+/// let names: Vec<&str> = player.iter().map(Track::get_name).collect();
+/// assert_eq!(names, ["scene:a", "scene:b"]);
+/// ```
+#[derive(Clone, Default)]
+pub struct OrderedPlayer {
+    tracks: BTreeMap<String, Track>,
+}
+
+impl OrderedPlayer {
+    /// Construct a new `OrderedPlayer` without tracks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the number of tracks.
+    pub fn len(&self) -> usize {
+        self.tracks.len()
+    }
+
+    /// Check if there are no tracks.
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
+
+    /// Add a track, and return the track it replaces if one with the same name exists.
+    pub fn insert(&mut self, track: Track) -> Option<Track> {
+        self.tracks.insert(track.get_name().to_owned(), track)
+    }
+
+    /// Remove a track by name, and return it.
+    pub fn remove(&mut self, name: &str) -> Option<Track> {
+        self.tracks.remove(name)
+    }
+
+    /// Get a track by name.
+    pub fn get(&self, name: &str) -> Option<&Track> {
+        self.tracks.get(name)
+    }
+
+    /// Get a track by name, for editing its keys.
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Track> {
+        self.tracks.get_mut(name)
+    }
+
+    /// Get the value of a track at `row`, or `None` if there's no track named `name`.
+    pub fn get_value(&self, name: &str, row: f32) -> Option<f32> {
+        self.get(name).map(|track| track.get_value(row))
+    }
 
-            // println!("  name {name_len} {name} {key_count}");
-            tracks.push(t);
-            // let name = bytes.
+    /// Iterate over the tracks, ordered by name.
+    pub fn iter(&self) -> impl Iterator<Item = &Track> + '_ {
+        self.tracks.values()
+    }
+}
+
+impl From<RocketPlayer> for OrderedPlayer {
+    fn from(player: RocketPlayer) -> Self {
+        Self {
+            tracks: player
+                .tracks
+                .into_iter()
+                .map(|track| (track.get_name().to_owned(), track))
+                .collect(),
         }
-        Self { tracks: tracks }
     }
 }
 
+impl From<OrderedPlayer> for RocketPlayer {
+    /// Get a `RocketPlayer` with the tracks, indexed in name order.
+    fn from(player: OrderedPlayer) -> Self {
+        RocketPlayer::new(player.tracks.into_values().collect())
+    }
+}
+
+/// The `RocketPlayerBuilder` Type. This builds a [`RocketPlayer`] one track at a time.
+///
+/// # Examples
+///
+/// ```
+/// # use rust_rocket::player::RocketPlayerBuilder;
+/// # use rust_rocket::track::{Track, TrackSource};
+/// let mut builder = RocketPlayerBuilder::new();
+/// builder.add_track(Track::new("camera:x")).unwrap();
+/// builder.add_track(Track::new("camera:y")).unwrap();
+/// assert!(builder.add_track(Track::new("camera:x")).is_err());
+/// let player = builder.build();
+/// assert_eq!(player.get_track_index("camera:y"), Some(1));
+/// ```
+pub struct RocketPlayerBuilder {
+    player: RocketPlayer,
+}
+
+impl Default for RocketPlayerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RocketPlayerBuilder {
+    /// Construct a new `RocketPlayerBuilder` without tracks.
+    pub fn new() -> Self {
+        Self {
+            player: RocketPlayer::with_capacity(0),
+        }
+    }
+
+    /// Add a track with the next index.
+    ///
+    /// # Errors
+    ///
+    /// [`DuplicateTrackError`] if a track with the same name was already added. The track isn't
+    /// added in that case.
+    pub fn add_track(&mut self, track: Track) -> Result<(), DuplicateTrackError> {
+        self.player.push(track)
+    }
+
+    /// Get the player with all added tracks.
+    pub fn build(self) -> RocketPlayer {
+        self.player
+    }
+}
+
+/// The `PlayerDecoder` Type. This deserializes tracks that arrive in chunks, e.g. over the
+/// network.
+///
+/// Each key is parsed as soon as its last byte has been [fed](PlayerDecoder::feed), so a large
+/// set of tracks is parsed progressively instead of all at once at the end, and no data is parsed
+/// twice. Data after a complete set of tracks is kept as the start of the next set.
+///
+/// A set of tracks can be at most [64 MiB](PlayerDecoder::with_max_size), so corrupt lengths
+/// can't make the decoder wait for, and buffer, more data forever.
+///
+/// # Examples
+///
+/// ```
+/// # use rust_rocket::player::PlayerDecoder;
+/// # use rust_rocket::track::TrackSource;
+/// // No tracks and then the start of the next set
+/// let data = [0, 0, 0, 0, 0, 0, 0, 0, 1];
+/// let mut decoder = PlayerDecoder::new();
+/// assert!(decoder.feed(&data[..5]).unwrap().is_none());
+/// let player = decoder.feed(&data[5..]).unwrap().unwrap();
+/// assert_eq!(player.track_count(), 0);
+/// assert!(decoder.finish().is_err());
+/// ```
+pub struct PlayerDecoder {
+    data: Vec<u8>,
+    /// Where the next unparsed part of the current set of tracks starts in `data`.
+    position: u64,
+    /// The most bytes a set of tracks may have.
+    max_size: u64,
+    /// The partially decoded player, and how many tracks it will have.
+    player: Option<(RocketPlayer, usize)>,
+    /// The partially decoded track, how many of its keys are still unparsed, and where its name
+    /// starts.
+    track: Option<(Track, usize, u64)>,
+}
+
+impl Default for PlayerDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PlayerDecoder {
+    /// Construct a new `PlayerDecoder` without data.
+    pub fn new() -> Self {
+        Self {
+            data: Vec::new(),
+            position: 0,
+            max_size: DEFAULT_MAX_DECODER_SIZE,
+            player: None,
+            track: None,
+        }
+    }
+
+    /// Set the most bytes a set of tracks may have. The default is 64 MiB.
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Add the next chunk of data, and get the player if it completes a set of tracks.
+    ///
+    /// Returns `None` if more data is needed. If the chunk also contains the start of the next
+    /// set of tracks, feed an empty chunk to check whether that set is complete too.
+    ///
+    /// # Errors
+    ///
+    /// The same as [`RocketPlayer::try_deserialize`], except that truncated data is not an error
+    /// until [`finish`](PlayerDecoder::finish) is called. Lengths are checked against the
+    /// [size limit](PlayerDecoder::with_max_size) instead of the data fed so far, so
+    /// [`DeserializeError::LengthOutOfBounds`] means that the set of tracks would be larger than
+    /// the limit. Offsets are relative to the start of the set of tracks. After an error, the
+    /// decoder returns the same error again.
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<Option<RocketPlayer>, DeserializeError> {
+        self.data.extend_from_slice(bytes);
+        match self.decode(self.max_size) {
+            Ok(player) => Ok(Some(player)),
+            Err(DeserializeError::UnexpectedEof { .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Get the player after all data has been fed.
+    ///
+    /// # Errors
+    ///
+    /// The same as [`RocketPlayer::try_deserialize`]. In particular,
+    /// [`DeserializeError::UnexpectedEof`] or [`DeserializeError::LengthOutOfBounds`] if the data
+    /// fed since the last complete set of tracks is truncated.
+    pub fn finish(mut self) -> Result<RocketPlayer, DeserializeError> {
+        let end = self.max_size.min(self.data.len() as u64);
+        self.decode(end)
+    }
+
+    /// Parse everything that has arrived, with lengths checked against the offset `end`, and
+    /// return the player if all tracks are parsed.
+    fn decode(&mut self, end: u64) -> Result<RocketPlayer, DeserializeError> {
+        let mut bytes = Cursor::new(&self.data[..]);
+        bytes.set_position(self.position);
+        if self.player.is_none() {
+            // The header is small, so it's parsed again from the start until it's complete
+            let metadata = format::read_header_within(&mut bytes, end)?;
+            let track_count = format::read_track_count_within(&mut bytes, end)?;
+            let mut player = RocketPlayer::with_capacity(track_count);
+            player.metadata = metadata;
+            self.player = Some((player, track_count));
+            self.position = bytes.position();
+        }
+
+        let (player, track_count) = self.player.as_mut().unwrap();
+        while player.track_count() < *track_count {
+            if self.track.is_none() {
+                let track_index = player.track_count();
+                // The name follows its `u64` length
+                let offset = bytes.position() + 8;
+                let (name, key_count) =
+                    format::read_track_header_within(&mut bytes, track_index, end)?;
+                // Checked before parsing the keys, so the error doesn't depend on how many
+                // arrived
+                if player.get_track_index(name).is_some() {
+                    return Err(DeserializeError::DuplicateName {
+                        offset,
+                        track_index,
+                        source: DuplicateTrackError(name.to_owned()),
+                    });
+                }
+                let track = Track::with_name(Cow::Owned(name.to_owned()), key_count);
+                self.track = Some((track, key_count, offset));
+                self.position = bytes.position();
+            }
+
+            let (track, key_count, _) = self.track.as_mut().unwrap();
+            while *key_count > 0 {
+                track.set_key(format::read_key(&mut bytes)?);
+                *key_count -= 1;
+                self.position = bytes.position();
+            }
+            let (track, _, _) = self.track.take().unwrap();
+            player.push(track).expect("Track names were checked");
+        }
+
+        let (player, _) = self.player.take().unwrap();
+        self.data.drain(..self.position as usize);
+        self.position = 0;
+        Ok(player)
+    }
+}
+
+/// Get the value of `track` at `row`, or its default if other tracks are soloed.
+fn solo_value(track: &Track, soloing: bool, row: f32) -> f32 {
+    if soloing && !track.is_soloed() {
+        return track.get_default();
+    }
+    track.get_value(row)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::format::{DELTA_DELETE_KEY, DELTA_MAGIC, HOLD_FLAG, MAGIC};
     use crate::interpolation::Interpolation;
     use crate::track::Key;
 
     fn get_test_tracks() -> Vec<Track> {
         vec![
-            {
-                let mut track = Track::new("test1");
-                track.set_key(Key::new(0, 1.0, Interpolation::Step));
-                track.set_key(Key::new(5, 0.0, Interpolation::Step));
-                track.set_key(Key::new(10, 1.0, Interpolation::Step));
-                track
-            },
-            {
-                let mut track = Track::new("test2");
-                track.set_key(Key::new(0, 2.0, Interpolation::Step));
-                track.set_key(Key::new(5, 0.0, Interpolation::Step));
-                track.set_key(Key::new(10, 2.0, Interpolation::Step));
-                track
-            },
+            Track::from_keys(
+                "test1",
+                vec![
+                    Key::new(0, 1.0, Interpolation::Step),
+                    Key::new(5, 0.0, Interpolation::Step),
+                    Key::new(10, 1.0, Interpolation::Step),
+                ],
+            ),
+            Track::from_keys(
+                "test2",
+                vec![
+                    Key::new(0, 2.0, Interpolation::Step),
+                    Key::new(5, 0.0, Interpolation::Step),
+                    Key::new(10, 2.0, Interpolation::Step),
+                ],
+            ),
         ]
     }
 
@@ -142,4 +835,391 @@ mod tests {
             2.0
         );
     }
+
+    #[test]
+    fn finds_tracks_by_handle() {
+        let player = RocketPlayer::new(get_test_tracks());
+        let handle = player.get_track_handle("test2").unwrap();
+        assert_eq!(handle.index(), 1);
+        assert_eq!(player.get_track_by_handle(handle).get_name(), "test2");
+        assert!(player.get_track_handle("test3").is_none());
+    }
+
+    #[test]
+    fn flattens_keys() {
+        let player = RocketPlayer::new(get_test_tracks());
+        let keys = player.all_keys();
+        assert_eq!(keys.len(), 6);
+        assert_eq!(
+            keys.iter().map(|(name, _)| *name).collect::<Vec<_>>(),
+            ["test1", "test1", "test1", "test2", "test2", "test2"]
+        );
+        assert!(*keys[3].1 == Key::new(0, 2.0, Interpolation::Step));
+        assert_eq!(keys[5].1.get_row(), 10);
+    }
+
+    #[test]
+    fn snapshots_values() {
+        let snapshot = RocketPlayer::new(get_test_tracks()).snapshot(10.);
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot["test1"], 1.0);
+        assert_eq!(snapshot["test2"], 2.0);
+    }
+
+    #[test]
+    fn soloes_tracks() {
+        let mut tracks = get_test_tracks();
+        tracks[1].set_default(-1.0);
+        let mut player = RocketPlayer::new(tracks);
+        assert_eq!(player.get_value(0, 10.), 1.0);
+        assert_eq!(player.get_value(1, 10.), 2.0);
+
+        player.tracks[0].set_soloed(true);
+        assert_eq!(player.get_value(0, 10.), 1.0);
+        assert_eq!(player.get_value(1, 10.), -1.0);
+        assert_eq!(player.snapshot(10.)["test2"], -1.0);
+        assert_eq!(player.sample(1, 10.), -1.0);
+        assert_eq!(
+            player.values_at(10.).collect::<Vec<_>>(),
+            [("test1", 1.0), ("test2", -1.0)]
+        );
+
+        player.tracks[0].set_muted(true);
+        assert_eq!(player.get_value(0, 10.), 0.0);
+    }
+
+    #[test]
+    fn loops_values() {
+        let mut track = Track::new("ramp");
+        track.set_key(Key::new(0, 0.0, Interpolation::Linear));
+        track.set_key(Key::new(10, 1.0, Interpolation::Linear));
+        let player = RocketPlayer::new(vec![track]);
+
+        assert_eq!(player.looped_value(0, 2.5, 10), 0.25);
+        assert_eq!(player.looped_value(0, 12.5, 10), 0.25);
+        assert_eq!(player.looped_value(0, -7.5, 10), 0.25);
+        assert_eq!(player.looped_value(0, 10., 10), 0.0);
+        assert_eq!(player.looped_value(0, 12.5, 0), 1.0);
+    }
+
+    #[test]
+    fn finds_duration() {
+        let mut tracks = get_test_tracks();
+        tracks[1].set_key(Key::new(20, 0.0, Interpolation::Step));
+        tracks.push(Track::new("empty"));
+        assert_eq!(RocketPlayer::new(tracks).duration(), 20);
+        assert_eq!(RocketPlayer::new(vec![]).duration(), 0);
+    }
+
+    #[test]
+    fn counts_keys() {
+        let mut tracks = get_test_tracks();
+        tracks.push(Track::new("empty"));
+        let player = RocketPlayer::new(tracks);
+        assert_eq!(player.key_count(0), 3);
+        assert_eq!(player.key_count(2), 0);
+        assert!(!player.track_is_empty(0));
+        assert!(player.track_is_empty(2));
+        assert_eq!(player.interpolation_counts(), [6, 0, 0, 0]);
+    }
+
+    #[test]
+    fn merges_players() {
+        let mut tracks = get_test_tracks();
+        let second = RocketPlayer::new(tracks.split_off(1));
+        let mut player = RocketPlayer::new(tracks);
+
+        player.merge(second).unwrap();
+        assert_eq!(player.track_count(), 2);
+        assert_eq!(player.get_track_index("test2"), Some(1));
+
+        let duplicate = RocketPlayer::new(vec![Track::new("test1"), Track::new("test3")]);
+        assert_eq!(player.merge(duplicate).unwrap_err().0, "test1");
+        assert_eq!(player.track_count(), 2);
+    }
+
+    fn get_test_data(name_len: u64, key_count: u64, interpolation: u32) -> Vec<u8> {
+        use byteorder::{WriteBytesExt, LE};
+
+        let mut data = vec![];
+        data.write_u64::<LE>(1).unwrap();
+        data.write_u64::<LE>(name_len).unwrap();
+        data.extend_from_slice(b"test");
+        data.write_u64::<LE>(key_count).unwrap();
+        data.write_u32::<LE>(0).unwrap();
+        data.write_f32::<LE>(1.0).unwrap();
+        data.write_u32::<LE>(interpolation).unwrap();
+        data
+    }
+
+    #[test]
+    fn reads_held_keys() {
+        let player = RocketPlayer::try_deserialize(&get_test_data(4, 1, HOLD_FLAG | 1)).unwrap();
+        let key = player.get_track(0).keys()[0];
+        assert!(key.is_held());
+        assert!(key.get_interpolation() == Interpolation::Linear);
+    }
+
+    #[test]
+    fn deserializes_valid_data() {
+        let player = RocketPlayer::try_deserialize(&get_test_data(4, 1, 0)).unwrap();
+        assert_eq!(
+            player
+                .get_track(player.get_track_index("test").unwrap())
+                .get_value(0.),
+            1.0
+        );
+    }
+
+    #[test]
+    fn borrows_static_names() {
+        let data: &'static [u8] = Box::leak(get_test_data(4, 1, 0).into_boxed_slice());
+        let player = RocketPlayer::try_deserialize_static(data).unwrap();
+        let name = player.get_track(0).get_name();
+        assert_eq!(name, "test");
+        assert_eq!(name.as_ptr(), data[16..].as_ptr());
+    }
+
+    #[test]
+    fn rejects_out_of_bounds_lengths() {
+        for (name_len, key_count) in [(1 << 40, 1), (u64::MAX, 1), (4, 1 << 40), (4, u64::MAX)] {
+            assert!(matches!(
+                RocketPlayer::try_deserialize(&get_test_data(name_len, key_count, 0)),
+                Err(DeserializeError::LengthOutOfBounds { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_data() {
+        let data = get_test_data(4, 1, 0);
+        assert!(matches!(
+            RocketPlayer::try_deserialize(&data[..4]),
+            Err(DeserializeError::UnexpectedEof {
+                offset: 0,
+                field: "track count",
+                ..
+            })
+        ));
+        assert!(matches!(
+            RocketPlayer::try_deserialize(&data[..data.len() - 1]),
+            Err(DeserializeError::LengthOutOfBounds {
+                offset: 20,
+                field: "key count",
+                ..
+            })
+        ));
+
+        let error = RocketPlayer::try_deserialize(&data[..data.len() - 1])
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "The key count 1 at byte 20 doesn't fit in the remaining 11 bytes of track data"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_names() {
+        let mut data = get_test_data(4, 1, 0);
+        data[17] = 0xff;
+        assert!(matches!(
+            RocketPlayer::try_deserialize(&data),
+            Err(DeserializeError::InvalidName {
+                offset: 16,
+                track_index: 0,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_empty_names() {
+        let mut data = get_test_data(0, 1, 0);
+        data.drain(16..20);
+        assert!(matches!(
+            RocketPlayer::try_deserialize(&data),
+            Err(DeserializeError::EmptyName {
+                offset: 16,
+                track_index: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn reads_metadata() {
+        use byteorder::{WriteBytesExt, LE};
+
+        let mut data = MAGIC.to_vec();
+        // One entry, followed by data from a future version that's skipped
+        data.write_u64::<LE>(8 + 8 + 1 + 8 + 2 + 3).unwrap();
+        data.write_u64::<LE>(1).unwrap();
+        data.write_u64::<LE>(1).unwrap();
+        data.extend_from_slice(b"a");
+        data.write_u64::<LE>(2).unwrap();
+        data.extend_from_slice(b"bc");
+        data.extend_from_slice(b"new");
+        data.extend_from_slice(&get_test_data(4, 1, 0));
+
+        let player = RocketPlayer::try_deserialize(&data).unwrap();
+        assert_eq!(player.metadata()["a"], "bc");
+        assert_eq!(player.get_track(0).get_name(), "test");
+
+        data[7] = 2;
+        assert!(matches!(
+            RocketPlayer::try_deserialize(&data),
+            Err(DeserializeError::UnsupportedVersion { version: 2 })
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_deltas() {
+        let mut player = RocketPlayer::new(get_test_tracks());
+        assert!(matches!(
+            player.apply_delta(&get_test_data(4, 1, 0)),
+            Err(DeserializeError::InvalidDelta { offset: 0 })
+        ));
+
+        let mut delta = DELTA_MAGIC.to_vec();
+        delta.extend_from_slice(&get_test_data(5, 2, 0)[..16]);
+        delta.extend_from_slice(b"test1");
+        delta.extend_from_slice(&[2, 0, 0, 0, 0, 0, 0, 0]);
+        delta.extend_from_slice(&[DELTA_DELETE_KEY, 5, 0, 0, 0]);
+        delta.extend_from_slice(&[2, 0, 0, 0, 0]);
+        assert!(matches!(
+            player.apply_delta(&delta),
+            Err(DeserializeError::InvalidDelta { offset: 42 })
+        ));
+        // Nothing was applied
+        assert_eq!(player.get_track(0).key_count(), 3);
+    }
+
+    #[test]
+    fn rejects_duplicate_names() {
+        assert_eq!(
+            RocketPlayer::try_new(vec![Track::new("a"), Track::new("b"), Track::new("a")])
+                .err()
+                .unwrap()
+                .0,
+            "a"
+        );
+
+        let mut data = get_test_data(4, 1, 0);
+        data[0] = 2;
+        data.extend_from_within(8..);
+        assert!(matches!(
+            RocketPlayer::try_deserialize(&data),
+            Err(DeserializeError::DuplicateName {
+                offset: 48,
+                track_index: 1,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_interpolation() {
+        assert!(matches!(
+            RocketPlayer::try_deserialize(&get_test_data(4, 1, 4)),
+            Err(DeserializeError::InvalidInterpolation {
+                offset: 36,
+                source: InvalidInterpolation(4)
+            })
+        ));
+    }
+
+    #[test]
+    fn builds_players() {
+        let mut builder = RocketPlayerBuilder::new();
+        for track in get_test_tracks() {
+            builder.add_track(track).unwrap();
+        }
+        assert_eq!(
+            builder.add_track(Track::new("test1")).err().unwrap().0,
+            "test1"
+        );
+        let player = builder.build();
+        assert_eq!(player.track_count(), 2);
+        assert_eq!(player.get_value(1, 0.), 2.0);
+    }
+
+    #[test]
+    fn decodes_in_chunks() {
+        let mut metadata = HashMap::new();
+        metadata.insert("build".to_owned(), "1234".to_owned());
+        let mut data = Vec::new();
+        format::write_header(&mut data, &metadata);
+        format::write_track_count(&mut data, 2);
+        for track in get_test_tracks() {
+            format::write_track(&mut data, &track);
+        }
+
+        // Byte by byte, followed by the same tracks once more
+        let mut decoder = PlayerDecoder::new();
+        let mut players = Vec::new();
+        for byte in data.iter().chain(&data) {
+            players.extend(decoder.feed(&[*byte]).unwrap());
+        }
+        assert_eq!(players.len(), 2);
+        for player in players {
+            assert_eq!(player.metadata(), &metadata);
+            assert_eq!(player.track_count(), 2);
+            assert_eq!(player.get_value(1, 10.), 2.0);
+        }
+
+        // Parsed keys are kept, the last key of the last track is incomplete
+        let mut decoder = PlayerDecoder::new();
+        assert!(decoder.feed(&data[..data.len() - 1]).unwrap().is_none());
+        assert!(matches!(decoder.track, Some((ref track, 1, _)) if track.key_count() == 2));
+        assert!(matches!(
+            decoder.finish(),
+            Err(DeserializeError::UnexpectedEof {
+                field: "key interpolation",
+                ..
+            })
+        ));
+
+        let mut data = get_test_data(4, 1, 4);
+        let mut decoder = PlayerDecoder::new();
+        assert!(decoder.feed(&data[..30]).unwrap().is_none());
+        assert!(matches!(
+            decoder.feed(&data.split_off(30)),
+            Err(DeserializeError::InvalidInterpolation { offset: 36, .. })
+        ));
+
+        // Lengths that can't fit in the size limit are errors, not missing data
+        let data = get_test_data(4, 1 << 40, 0);
+        let mut decoder = PlayerDecoder::new();
+        assert!(matches!(
+            decoder.feed(&data[..30]),
+            Err(DeserializeError::LengthOutOfBounds {
+                field: "key count",
+                ..
+            })
+        ));
+        let data = get_test_data(4, 1, 0);
+        let mut decoder = PlayerDecoder::new().with_max_size(data.len() as u64 - 1);
+        assert!(matches!(
+            decoder.feed(&data),
+            Err(DeserializeError::LengthOutOfBounds { .. })
+        ));
+        let mut decoder = PlayerDecoder::new().with_max_size(data.len() as u64);
+        assert!(decoder.feed(&data).unwrap().is_some());
+    }
+
+    #[test]
+    fn orders_tracks_by_name() {
+        let mut player = OrderedPlayer::from(RocketPlayer::new(get_test_tracks()));
+        player.insert(Track::new("a"));
+        assert!(player.insert(Track::new("test2")).is_some());
+        assert_eq!(player.len(), 3);
+        assert_eq!(player.get_value("test1", 10.), Some(1.0));
+        assert_eq!(player.get_value("test2", 10.), Some(0.0));
+        assert!(player.remove("test1").is_some());
+        assert!(player.get("test1").is_none());
+
+        let player = RocketPlayer::from(player);
+        assert_eq!(player.get_track_index("a"), Some(0));
+        assert_eq!(player.get_track_index("test2"), Some(1));
+    }
 }
@@ -0,0 +1,324 @@
+//! This module contains [`SessionRecorder`] and [`ReplaySource`] for recording and replaying
+//! tracker sessions.
+//!
+//! A recording contains every command received from the tracker, with the time since recording
+//! started. Replaying it to a client reproduces the same [`Event`](crate::client::Event)s and key
+//! edits, so bugs reported during an editing session can be reproduced offline.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! # use rust_rocket::RocketClient;
+//! # use rust_rocket::session::{ReplaySource, SessionRecorder};
+//! // Record a session
+//! let mut rocket = RocketClient::new().unwrap();
+//! let recorder = SessionRecorder::create("session.bin").unwrap();
+//! recorder.attach(&mut rocket);
+//! // ...
+//! recorder.flush().unwrap();
+//!
+//! // Replay it later, twice as fast
+//! let addr = ReplaySource::open("session.bin")
+//!     .unwrap()
+//!     .speed(2.0)
+//!     .listen("127.0.0.1:0")
+//!     .unwrap();
+//! let mut rocket = RocketClient::connect(addr).unwrap();
+//! ```
+use crate::client::{Direction, RocketClient};
+
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use std::{
+    convert::TryFrom,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, ToSocketAddrs},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Identifies session recordings. The last byte is the format version.
+const MAGIC: &[u8; 8] = b"RKTSESS\x01";
+
+/// The length of the longest command in a recording. Rocket commands are at most 14 bytes long,
+/// so longer lengths mean that a recording is corrupt.
+const MAX_COMMAND_LEN: usize = 1024;
+
+struct Recording<W> {
+    writer: W,
+    error: Option<std::io::Error>,
+}
+
+/// The `SessionRecorder` Type. This writes commands received by a [`RocketClient`] to a file or
+/// another writer.
+///
+/// Each command is written with the time since the recorder was created.
+/// The recorder can be cloned to keep a handle after [attaching](SessionRecorder::attach) it.
+pub struct SessionRecorder<W> {
+    recording: Arc<Mutex<Recording<W>>>,
+    start: Instant,
+}
+
+impl<W> Clone for SessionRecorder<W> {
+    fn clone(&self) -> Self {
+        Self {
+            recording: self.recording.clone(),
+            start: self.start,
+        }
+    }
+}
+
+impl SessionRecorder<BufWriter<File>> {
+    /// Construct a new `SessionRecorder` writing to a new file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Any error from creating the file or writing the header.
+    pub fn create<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write + Send + 'static> SessionRecorder<W> {
+    /// Construct a new `SessionRecorder` writing to `writer`.
+    ///
+    /// # Errors
+    ///
+    /// Any error from writing the header.
+    pub fn new(mut writer: W) -> std::io::Result<Self> {
+        writer.write_all(MAGIC)?;
+        Ok(Self {
+            recording: Arc::new(Mutex::new(Recording {
+                writer,
+                error: None,
+            })),
+            start: Instant::now(),
+        })
+    }
+
+    /// Record a received command.
+    ///
+    /// # Errors
+    ///
+    /// Any error from writing to the writer, including an earlier error from an attached client,
+    /// or [`std::io::ErrorKind::InvalidInput`] if `command` is longer than 1024 bytes.
+    pub fn record(&self, command: &[u8]) -> std::io::Result<()> {
+        if command.len() > MAX_COMMAND_LEN {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Command too long",
+            ));
+        }
+        let mut recording = self.recording.lock().unwrap();
+        if let Some(error) = recording.error.take() {
+            return Err(error);
+        }
+        let time = u64::try_from(self.start.elapsed().as_micros()).unwrap_or(u64::MAX);
+        let writer = &mut recording.writer;
+        writer.write_u64::<LE>(time)?;
+        writer.write_u32::<LE>(command.len() as u32)?;
+        writer.write_all(command)
+    }
+
+    /// Record every command `client` receives from now on.
+    ///
+    /// This replaces the client's [packet logger](RocketClient::set_packet_logger).
+    /// Write errors are kept and returned from the next [`record`](SessionRecorder::record) or
+    /// [`flush`](SessionRecorder::flush).
+    pub fn attach(&self, client: &mut RocketClient) {
+        let recorder = self.clone();
+        client.set_packet_logger(move |direction, command| {
+            if direction == Direction::Received {
+                if let Err(error) = recorder.record(command) {
+                    recorder
+                        .recording
+                        .lock()
+                        .unwrap()
+                        .error
+                        .get_or_insert(error);
+                }
+            }
+        });
+    }
+
+    /// Flush the writer.
+    ///
+    /// # Errors
+    ///
+    /// Any error from flushing, or an earlier error from an attached client.
+    pub fn flush(&self) -> std::io::Result<()> {
+        let mut recording = self.recording.lock().unwrap();
+        if let Some(error) = recording.error.take() {
+            return Err(error);
+        }
+        recording.writer.flush()
+    }
+}
+
+/// The `ReplaySource` Type. This is a fake tracker that replays a recorded session.
+///
+/// Clients connect to it just like to a real tracker. Recorded commands are sent with their
+/// original timing, adjusted by the [speed](ReplaySource::speed). Anything the client sends is
+/// ignored, so the client must register the same tracks in the same order as when recording,
+/// before polling for events. The connection is closed after the last command.
+pub struct ReplaySource {
+    commands: Vec<(Duration, Vec<u8>)>,
+    speed: f64,
+}
+
+impl ReplaySource {
+    /// Read a recording from a file.
+    ///
+    /// # Errors
+    ///
+    /// Any error from reading the file, or [`std::io::ErrorKind::InvalidData`] if it's not a
+    /// session recording.
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Read a recording.
+    ///
+    /// # Errors
+    ///
+    /// Any error from reading, or [`std::io::ErrorKind::InvalidData`] if it's not a session
+    /// recording or a command is longer than 1024 bytes.
+    pub fn from_reader(mut reader: impl Read) -> std::io::Result<Self> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Not a Rocket session recording",
+            ));
+        }
+
+        let mut commands = Vec::new();
+        loop {
+            let time = match reader.read_u64::<LE>() {
+                Ok(time) => Duration::from_micros(time),
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            };
+            // Check the length before allocating, corrupt lengths can be up to 4 GiB
+            let len = reader.read_u32::<LE>()? as usize;
+            if len > MAX_COMMAND_LEN {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "Command too long",
+                ));
+            }
+            let mut command = vec![0; len];
+            reader.read_exact(&mut command)?;
+            commands.push((time, command));
+        }
+
+        Ok(Self {
+            commands,
+            speed: 1.0,
+        })
+    }
+
+    /// Set the playback speed. `1.0` is the original timing, `2.0` is twice as fast, and
+    /// [`f64::INFINITY`] sends all commands without waiting.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `speed` is not positive.
+    pub fn speed(mut self, speed: f64) -> Self {
+        assert!(speed > 0.0, "Replay speed must be positive");
+        self.speed = speed;
+        self
+    }
+
+    /// Start listening for a client on `addr`, and replay the session to the first client that
+    /// connects.
+    ///
+    /// The replay runs in a background thread. Returns the address to connect to, which is useful
+    /// for listening on port 0.
+    ///
+    /// # Errors
+    ///
+    /// Any error from binding to `addr`.
+    pub fn listen(self, addr: impl ToSocketAddrs) -> std::io::Result<SocketAddr> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        std::thread::spawn(move || {
+            // Errors mean that the client went away, which ends the replay
+            let _ = self.replay(listener);
+        });
+        Ok(addr)
+    }
+
+    fn replay(self, listener: TcpListener) -> std::io::Result<()> {
+        let (mut stream, _) = listener.accept()?;
+        let mut greeting = [0; 19];
+        stream.read_exact(&mut greeting)?;
+        stream.write_all(b"hello, demo!")?;
+
+        let mut reader = stream.try_clone()?;
+        std::thread::spawn(move || std::io::copy(&mut reader, &mut std::io::sink()));
+
+        let start = Instant::now();
+        for (time, command) in self.commands {
+            let time = Duration::from_secs_f64(time.as_secs_f64() / self.speed);
+            if let Some(delay) = time.checked_sub(start.elapsed()) {
+                std::thread::sleep(delay);
+            }
+            stream.write_all(&command)?;
+        }
+        stream.shutdown(Shutdown::Write)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Event;
+    use crate::track::{TrackEditor, TrackSource};
+
+    #[test]
+    fn replays_recording() {
+        let recorder = SessionRecorder::new(Vec::new()).unwrap();
+        // SET_KEY on track 0, row 3, value 1.0, linear
+        recorder
+            .record(&[0, 0, 0, 0, 0, 0, 0, 0, 3, 0x3f, 0x80, 0, 0, 1])
+            .unwrap();
+        // SET_ROW 7
+        recorder.record(&[3, 0, 0, 0, 7]).unwrap();
+        let recording = std::mem::take(&mut recorder.recording.lock().unwrap().writer);
+
+        let addr = ReplaySource::from_reader(&recording[..])
+            .unwrap()
+            .speed(f64::INFINITY)
+            .listen("127.0.0.1:0")
+            .unwrap();
+        let mut client = RocketClient::connect(addr).unwrap();
+        let track = client.get_track_index_mut("test").unwrap();
+
+        let event = loop {
+            if let Some(event) = client.poll_events().unwrap() {
+                break event;
+            }
+        };
+        assert!(matches!(event, Event::SetRow(7)));
+        assert_eq!(client.get_track(track).get_value(3.), 1.0);
+
+        // The connection is closed after the last command
+        while client.poll_events().is_ok() {}
+    }
+
+    #[test]
+    fn rejects_long_commands() {
+        let recorder = SessionRecorder::new(Vec::new()).unwrap();
+        let error = recorder.record(&[0; MAX_COMMAND_LEN + 1]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+
+        let mut recording = MAGIC.to_vec();
+        recording.write_u64::<LE>(0).unwrap();
+        recording.write_u32::<LE>(u32::MAX).unwrap();
+        let error = ReplaySource::from_reader(&recording[..]).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,314 @@
+//! This module contains [`Controls`] and [`EventReceiver`] for polling a [`RocketClient`] in a
+//! background thread.
+//!
+//! See [`RocketClient::spawn_events`].
+use crate::client::{Error, Event, RocketClient};
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+/// How long the polling thread sleeps when the tracker is idle.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// A bounded queue of events, which makes room by dropping [`Event::SetRow`]s superseded by a
+/// later row.
+struct EventQueue {
+    events: VecDeque<Event>,
+    capacity: usize,
+    closed: bool,
+}
+
+impl EventQueue {
+    fn push(&mut self, event: Event) {
+        if self.events.len() >= self.capacity {
+            // Only the latest row matters, but pauses, save requests and the latest row itself
+            // must not be lost
+            let rows = self
+                .events
+                .iter()
+                .filter(|e| matches!(e, Event::SetRow(_)))
+                .count();
+            let latest_rows = if matches!(event, Event::SetRow(_)) {
+                0
+            } else {
+                1
+            };
+            if rows > latest_rows {
+                let pos = self
+                    .events
+                    .iter()
+                    .position(|e| matches!(e, Event::SetRow(_)))
+                    .unwrap();
+                self.events.remove(pos);
+            }
+        }
+        self.events.push_back(event);
+    }
+}
+
+struct Shared {
+    queue: Mutex<EventQueue>,
+    available: Condvar,
+}
+
+/// The `EventReceiver` Type. This receives events from a client polled in a background thread,
+/// see [`RocketClient::spawn_events`].
+pub struct EventReceiver {
+    shared: Arc<Shared>,
+}
+
+impl EventReceiver {
+    /// Wait for the next event.
+    ///
+    /// Returns `None` once the polling thread has stopped and all events have been received.
+    pub fn recv(&self) -> Option<Event> {
+        let mut queue = self.shared.queue.lock().unwrap();
+        loop {
+            if let Some(event) = queue.events.pop_front() {
+                return Some(event);
+            }
+            if queue.closed {
+                return None;
+            }
+            queue = self.shared.available.wait(queue).unwrap();
+        }
+    }
+
+    /// Wait for the next event for at most `timeout`.
+    ///
+    /// Returns `None` if no event was received in time, or once the polling thread has stopped
+    /// and all events have been received.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Event> {
+        let start = Instant::now();
+        let mut queue = self.shared.queue.lock().unwrap();
+        loop {
+            if let Some(event) = queue.events.pop_front() {
+                return Some(event);
+            }
+            if queue.closed {
+                return None;
+            }
+            let remaining = timeout.checked_sub(start.elapsed())?;
+            queue = self
+                .shared
+                .available
+                .wait_timeout(queue, remaining)
+                .unwrap()
+                .0;
+        }
+    }
+
+    /// Get the next event if there is one, without waiting.
+    pub fn try_recv(&self) -> Option<Event> {
+        self.shared.queue.lock().unwrap().events.pop_front()
+    }
+}
+
+/// Iterate over events with [`recv`](EventReceiver::recv), until the polling thread has stopped.
+impl Iterator for &EventReceiver {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        self.recv()
+    }
+}
+
+/// The `Controls` Type. This gives access to a client polled in a background thread, see
+/// [`RocketClient::spawn_events`].
+pub struct Controls {
+    client: Arc<Mutex<RocketClient>>,
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<Result<(), Error>>,
+}
+
+impl Controls {
+    /// Lock the client, e.g. to read tracks or set the row.
+    ///
+    /// The polling thread waits while the client is locked, so don't keep it locked for long.
+    pub fn client(&self) -> MutexGuard<'_, RocketClient> {
+        self.client.lock().unwrap()
+    }
+
+    /// Stop the polling thread, and get the client back.
+    ///
+    /// Events that were not received yet are dropped with the [`EventReceiver`].
+    ///
+    /// # Errors
+    ///
+    /// Any error that stopped the polling thread earlier, such as [`Error::IOError`] if the
+    /// tracker disconnected.
+    pub fn stop(self) -> Result<RocketClient, Error> {
+        self.stop.store(true, Ordering::Relaxed);
+        self.thread
+            .join()
+            .expect("Rocket polling thread panicked")?;
+        match Arc::try_unwrap(self.client) {
+            Ok(client) => Ok(client.into_inner().unwrap()),
+            Err(_) => unreachable!("The polling thread has stopped"),
+        }
+    }
+}
+
+impl RocketClient {
+    /// Poll for events in a background thread.
+    ///
+    /// The thread receives events and applies key edits as fast as the tracker sends them, even
+    /// if the consumer is slow. Events are queued in the returned [`EventReceiver`], and the
+    /// client is available through the returned [`Controls`].
+    ///
+    /// At most `capacity` events are queued. When the queue is full, the oldest
+    /// [`Event::SetRow`] is dropped to make room if a later row supersedes it, since only the
+    /// latest row matters. [`Event::Pause`], [`Event::SaveTracks`] and the latest
+    /// [`Event::SetRow`] are never dropped, so they can exceed the capacity if no superseded row
+    /// events are queued. Key edits aren't events, they are applied to the
+    /// client's tracks directly.
+    ///
+    /// The thread stops on errors, see [`Controls::stop`].
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `capacity` is `0`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use rust_rocket::RocketClient;
+    /// # use rust_rocket::client::Event;
+    /// # use rust_rocket::track::{TrackEditor, TrackSource};
+    /// let mut rocket = RocketClient::new().unwrap();
+    /// let track = rocket.get_track_index_mut("test").unwrap();
+    /// let (controls, events) = rocket.spawn_events(16);
+    /// while let Some(event) = events.recv() {
+    ///     if let Event::SetRow(row) = event {
+    ///         println!("{}", controls.client().get_track(track).get_value(row as f32));
+    ///     }
+    /// }
+    /// let rocket = controls.stop();
+    /// ```
+    pub fn spawn_events(self, capacity: usize) -> (Controls, EventReceiver) {
+        assert!(capacity > 0, "Event queue capacity must be positive");
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(EventQueue {
+                events: VecDeque::with_capacity(capacity),
+                capacity,
+                closed: false,
+            }),
+            available: Condvar::new(),
+        });
+        let client = Arc::new(Mutex::new(self));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let shared = shared.clone();
+            let client = client.clone();
+            let stop = stop.clone();
+            std::thread::spawn(move || {
+                let result = poll_loop(&client, &shared, &stop);
+                shared.queue.lock().unwrap().closed = true;
+                shared.available.notify_all();
+                result
+            })
+        };
+
+        (
+            Controls {
+                client,
+                stop,
+                thread,
+            },
+            EventReceiver { shared },
+        )
+    }
+}
+
+fn poll_loop(
+    client: &Mutex<RocketClient>,
+    shared: &Shared,
+    stop: &AtomicBool,
+) -> Result<(), Error> {
+    while !stop.load(Ordering::Relaxed) {
+        let event = client.lock().unwrap().poll_events()?;
+        match event {
+            Some(event) => {
+                shared.queue.lock().unwrap().push(event);
+                shared.available.notify_one();
+            }
+            None => std::thread::sleep(POLL_INTERVAL),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::tests::connect;
+    use std::io::Write;
+
+    #[test]
+    fn coalesces_rows() {
+        let mut queue = EventQueue {
+            events: VecDeque::new(),
+            capacity: 2,
+            closed: false,
+        };
+        queue.push(Event::SetRow(1));
+        queue.push(Event::SetRow(2));
+        queue.push(Event::Pause(true));
+        queue.push(Event::SetRow(3));
+        queue.push(Event::SaveTracks);
+        queue.push(Event::Pause(false));
+        assert!(matches!(
+            queue.events.make_contiguous(),
+            [
+                Event::Pause(true),
+                Event::SetRow(3),
+                Event::SaveTracks,
+                Event::Pause(false)
+            ]
+        ));
+
+        // A new row supersedes the queued one
+        queue.push(Event::SetRow(4));
+        assert!(matches!(
+            queue.events.make_contiguous(),
+            [
+                Event::Pause(true),
+                Event::SaveTracks,
+                Event::Pause(false),
+                Event::SetRow(4)
+            ]
+        ));
+    }
+
+    #[test]
+    fn polls_in_background() {
+        let (client, mut tracker) = connect();
+        let (controls, events) = client.spawn_events(4);
+        assert!(events.recv_timeout(Duration::from_millis(10)).is_none());
+
+        // SET_ROW 5
+        tracker.write_all(&[3, 0, 0, 0, 5]).unwrap();
+        assert!(matches!(
+            events.recv_timeout(Duration::from_secs(5)),
+            Some(Event::SetRow(5))
+        ));
+        assert_eq!(controls.client().sync().unwrap().row, 5);
+
+        // SAVE_TRACKS
+        tracker.write_all(&[5]).unwrap();
+        assert!(matches!((&events).next(), Some(Event::SaveTracks)));
+
+        let client = controls.stop().unwrap();
+        drop(client);
+        assert!(events.recv().is_none());
+        assert_eq!((&events).count(), 0);
+    }
+}
@@ -2,82 +2,576 @@
 
 
 use crate::interpolation::*;
-use std::io::Write;
-use byteorder::{LE, WriteBytesExt};
+use std::{borrow::Cow, collections::BTreeMap, sync::Arc};
 
-pub trait RocketEngine {
+/// The `TrackSource` Trait. This looks up tracks, and is implemented by both
+/// [`RocketClient`](crate::RocketClient) and [`RocketPlayer`](crate::RocketPlayer).
+pub trait TrackSource {
     fn get_track_index(&self, name: &str) -> Option<usize>;
-    fn get_track_index_mut(&mut self, name: &str) -> Result<usize, std::io::Error>;
     fn get_track(&self, index: usize) ->&Track;
+
+    /// Get a handle to a track by name.
+    ///
+    /// Like [`get_track_index`](TrackSource::get_track_index), but the result can't be mixed up
+    /// with other numbers.
+    fn get_track_handle(&self, name: &str) -> Option<TrackHandle> {
+        self.get_track_index(name).map(TrackHandle)
+    }
+
+    /// Get a track by handle.
+    ///
+    /// The handle must come from the same engine.
+    fn get_track_by_handle(&self, handle: TrackHandle) -> &Track {
+        self.get_track(handle.0)
+    }
+
+    /// Get the number of keys in a track.
+    fn key_count(&self, index: usize) -> usize {
+        self.get_track(index).key_count()
+    }
+
+    /// Check if a track has no keys, i.e. isn't animated.
+    fn track_is_empty(&self, index: usize) -> bool {
+        self.get_track(index).is_empty()
+    }
+
+    /// Get the value of a track at `row`.
+    ///
+    /// This is [`Track::get_value`] by default. [`RocketPlayer`](crate::RocketPlayer) also honors
+    /// solo, like [`RocketPlayer::get_value`](crate::RocketPlayer::get_value).
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `index` is not a valid track index.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::RocketPlayer;
+    /// # use rust_rocket::track::{Track, TrackSource};
+    /// fn camera_x(tracks: &impl TrackSource, row: f32) -> f32 {
+    ///     tracks.get_track_index("camera:x").map_or(0.0, |index| tracks.sample(index, row))
+    /// }
+    ///
+    /// let mut track = Track::new("camera:x");
+    /// track.set_default(2.0);
+    /// assert_eq!(camera_x(&RocketPlayer::new(vec![track]), 5.0), 2.0);
+    /// ```
+    fn sample(&self, index: usize, row: f32) -> f32 {
+        self.get_track(index).get_value(row)
+    }
+}
+
+/// The `TrackEditor` Trait. This creates and edits tracks, and is implemented by
+/// [`RocketClient`](crate::RocketClient).
+pub trait TrackEditor: TrackSource {
+    fn get_track_index_mut(&mut self, name: &str) -> Result<usize, std::io::Error>;
+
+    /// Insert or update a key on a track.
+    fn set_key(&mut self, track: usize, key: Key) -> Result<(), std::io::Error>;
+
+    /// Get a handle to a track by name, creating the track if it doesn't exist.
+    ///
+    /// Like [`get_track_index_mut`](TrackEditor::get_track_index_mut), but the result can't be
+    /// mixed up with other numbers.
+    fn get_track_handle_mut(&mut self, name: &str) -> Result<TrackHandle, std::io::Error> {
+        self.get_track_index_mut(name).map(TrackHandle)
+    }
 }
 
-#[derive(Clone, Copy)]
+/// The former name of [`TrackSource`], from before creating and editing tracks moved to
+/// [`TrackEditor`].
+pub use TrackSource as RocketEngine;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+/// The `TrackHandle` Type. This is a resolved track index, see
+/// [`TrackSource::get_track_handle`].
+pub struct TrackHandle(usize);
+
+impl TrackHandle {
+    /// Get the track index, for use with index-based APIs.
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// A row of a track.
+///
+/// The Rocket protocol and the serialized track format store rows as `u32`. Rows beyond
+/// [`u32::MAX`] can be used locally, but sending them to the tracker fails with
+/// [`Error::RowOverflow`](crate::client::Error::RowOverflow), and
+/// [writing](crate::format::write_key) them to the track format panics.
+pub type Row = u64;
+
+#[derive(Clone, Copy, PartialEq)]
 #[cfg_attr(feature = "debug", derive(Debug))]
 /// The `Key` Type.
 pub struct Key {
-    row: u32,
+    row: Row,
     value: f32,
     interpolation: Interpolation,
+    hold: bool,
 }
 
 impl Key {
     /// Construct a new `Key`.
-    pub fn new(row: u32, value: f32, interp: Interpolation) -> Key {
+    pub fn new(row: Row, value: f32, interp: Interpolation) -> Key {
         Key {
             row,
             value,
             interpolation: interp,
+            hold: false,
         }
     }
+
+    /// Construct a new `Key` with an `f64` value, for keys computed in `f64`.
+    ///
+    /// The value is narrowed to `f32`, like the Rocket protocol and the serialized format store
+    /// it. This rounds to about 7 significant digits, and values beyond the range of `f32`
+    /// become infinite.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::track::Key;
+    /// # use rust_rocket::interpolation::Interpolation;
+    /// let key = Key::new_f64(0, std::f64::consts::PI, Interpolation::Linear);
+    /// assert_eq!(key.get_value(), std::f32::consts::PI);
+    /// ```
+    pub fn new_f64(row: Row, value: f64, interp: Interpolation) -> Key {
+        Key::new(row, value as f32, interp)
+    }
+
+    /// Get a copy of this key that holds its value until the next key, or doesn't.
+    ///
+    /// A held key's value is used for all rows up to the next key, like with
+    /// [`Interpolation::Step`], but regardless of the
+    /// [interpolation source](Track::set_interpolation_source),
+    /// [custom eases](Track::set_custom_ease) and interpolation overrides. This encodes sections
+    /// in a single track: a held key ends a section even if the next key's interpolation would
+    /// drive the segment. The key's own interpolation is kept for when the hold is removed.
+    ///
+    /// Holds are local: the Rocket protocol can't send them, so trackers show held keys with
+    /// their interpolation, and keys received from the tracker replace held keys. Serialized
+    /// tracks keep holds in the highest bit of the interpolation, which parsers from before holds
+    /// reject as an invalid interpolation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::track::{InterpolationSource, Key, Track};
+    /// # use rust_rocket::interpolation::Interpolation;
+    /// let mut track = Track::new("test");
+    /// track.set_interpolation_source(InterpolationSource::SegmentEnd);
+    /// track.set_key(Key::new(0, 1.0, Interpolation::Linear).with_hold(true));
+    /// track.set_key(Key::new(10, 0.0, Interpolation::Linear));
+    /// assert_eq!(track.get_value(5.), 1.0);
+    /// ```
+    pub fn with_hold(mut self, hold: bool) -> Key {
+        self.hold = hold;
+        self
+    }
+
+    /// Check if the key [holds](Key::with_hold) its value until the next key.
+    pub fn is_held(&self) -> bool {
+        self.hold
+    }
+
+    /// Get the row of the key.
+    pub fn get_row(&self) -> Row {
+        self.row
+    }
+
+    /// Get the value of the key.
+    pub fn get_value(&self) -> f32 {
+        self.value
+    }
+
+    /// Get the interpolation of the key.
+    pub fn get_interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+/// The `KeyChange` Type. This is a difference between two tracks, see [`Track::diff`].
+pub enum KeyChange {
+    /// A key only exists in the other track.
+    Added(Key),
+    /// A key at this row only exists in this track.
+    Removed(Row),
+    /// The keys at this row differ.
+    Modified { row: Row, old: Key, new: Key },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+/// The `InterpolationSource` Type.
+/// This selects which key's interpolation is used between two keys.
+pub enum InterpolationSource {
+    /// The key at the start of a segment drives it. This is the Rocket convention.
+    #[default]
+    SegmentStart,
+    /// The key at the end of a segment drives it.
+    SegmentEnd,
+}
+
+/// Result of [`Track::get_segment_position`].
+enum SegmentPosition {
+    /// The row is not between two keys, and has this value.
+    Outside(f32),
+    /// The row is between the key at this position and the next one.
+    Inside(usize),
+}
+
+/// A custom [`Ease`] for a key, see [`Track::set_custom_ease`].
+#[derive(Clone)]
+struct CustomEase(Arc<dyn Ease + Send + Sync>);
+
+#[cfg(feature = "debug")]
+impl std::fmt::Debug for CustomEase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CustomEase")
+    }
 }
 
 #[derive(Clone)]
 #[cfg_attr(feature = "debug", derive(Debug))]
 /// The `Track` Type. This is a collection of `Key`s with a name.
+///
+/// Names of tracks loaded from data embedded in the binary with
+/// [`RocketPlayer::try_deserialize_static`](crate::RocketPlayer::try_deserialize_static) are
+/// borrowed instead of copied. Names can only be borrowed from `'static` data, so names of tracks
+/// loaded from other buffers, such as memory maps, are always copied.
 pub struct Track {
-    name: String,
+    name: Cow<'static, str>,
     keys: Vec<Key>,
+    default: f32,
+    interpolation_source: InterpolationSource,
+    muted: bool,
+    soloed: bool,
+    custom_eases: BTreeMap<Row, CustomEase>,
 }
 
 impl Track {
     /// Construct a new Track with a name.
     pub fn new<S: Into<String>>(name: S) -> Track {
-        Track {
-            name: name.into(),
-            keys: Vec::new(),
-        }
+        Track::with_name(Cow::Owned(name.into()), 0)
     }
     pub fn with_capacity<S: Into<String>>(name: S, keys: usize) -> Track {
+        Track::with_name(Cow::Owned(name.into()), keys)
+    }
+
+    /// Construct a new Track with a possibly borrowed name, and room for `keys` keys.
+    pub(crate) fn with_name(name: Cow<'static, str>, keys: usize) -> Track {
         Track {
-            name: name.into(),
+            name,
             keys: Vec::with_capacity(keys),
+            default: 0.0,
+            interpolation_source: InterpolationSource::SegmentStart,
+            muted: false,
+            soloed: false,
+            custom_eases: BTreeMap::new(),
         }
     }
 
+    /// Construct a new Track with a name and keys.
+    ///
+    /// Keys don't need to be sorted. If several keys have the same row, the last one is used.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::track::{Key, Track};
+    /// # use rust_rocket::interpolation::Interpolation;
+    /// let track = Track::from_keys(
+    ///     "test",
+    ///     vec![
+    ///         Key::new(10, 1.0, Interpolation::Step),
+    ///         Key::new(0, 0.0, Interpolation::Linear),
+    ///     ],
+    /// );
+    /// assert_eq!(track.get_value(5.), 0.5);
+    /// ```
+    pub fn from_keys<S: Into<String>>(name: S, keys: impl IntoIterator<Item = Key>) -> Track {
+        let mut keys: Vec<Key> = keys.into_iter().collect();
+        // Stable sort keeps keys with the same row in order, reversing lets dedup keep the last
+        keys.sort_by_key(|k| k.row);
+        keys.reverse();
+        keys.dedup_by_key(|k| k.row);
+        keys.reverse();
+
+        let mut track = Track::new(name);
+        track.keys = keys;
+        track
+    }
+
+    /// Construct a new Track with a name, and keys at `rows` with the values of `f`.
+    ///
+    /// All keys use `interp`. Rows are handled like in [`from_keys`](Track::from_keys).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::track::Track;
+    /// # use rust_rocket::interpolation::Interpolation;
+    /// let track = Track::from_fn("sine", (0..64).step_by(4), Interpolation::Smooth, |row| {
+    ///     (row as f32 * 0.1).sin()
+    /// });
+    /// assert_eq!(track.key_count(), 16);
+    /// ```
+    pub fn from_fn<S: Into<String>, F: Fn(Row) -> f32>(
+        name: S,
+        rows: impl IntoIterator<Item = Row>,
+        interp: Interpolation,
+        f: F,
+    ) -> Track {
+        Track::from_keys(
+            name,
+            rows.into_iter().map(|row| Key::new(row, f(row), interp)),
+        )
+    }
+
     /// Get the name of the track.
     pub fn get_name(&self) -> &str {
-        self.name.as_str()
+        &self.name
+    }
+
+    /// Get the name of the track, without losing whether it's borrowed.
+    pub(crate) fn get_name_cow(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+
+    /// Set the value returned by [`get_value`](Track::get_value) while the track has no keys.
+    ///
+    /// The default is `0.0`. It has no effect on tracks with keys.
+    pub fn set_default(&mut self, default: f32) {
+        self.default = default;
+    }
+
+    /// Get the value returned by [`get_value`](Track::get_value) while the track has no keys.
+    pub fn get_default(&self) -> f32 {
+        self.default
+    }
+
+    /// Set which key's interpolation is used between two keys.
+    ///
+    /// The default is [`InterpolationSource::SegmentStart`], matching GNU Rocket.
+    /// Use [`InterpolationSource::SegmentEnd`] for tracks imported from tools where the key at the
+    /// end of a segment defines how the segment is eased.
+    pub fn set_interpolation_source(&mut self, source: InterpolationSource) {
+        self.interpolation_source = source;
+    }
+
+    /// Get which key's interpolation is used between two keys.
+    pub fn get_interpolation_source(&self) -> InterpolationSource {
+        self.interpolation_source
     }
 
-    fn get_exact_position(&self, row: u32) -> Option<usize> {
+    /// Mute or unmute the track.
+    ///
+    /// A muted track's [`get_value`](Track::get_value) returns the [default](Track::set_default),
+    /// as if it had no keys. The keys are not changed.
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    /// Check if the track is muted.
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    /// Solo or unsolo the track.
+    ///
+    /// This has no effect on the track itself. While any track of a
+    /// [`RocketPlayer`](crate::RocketPlayer) is soloed, the player treats all tracks that aren't
+    /// soloed as muted, see [`RocketPlayer::get_value`](crate::RocketPlayer::get_value).
+    pub fn set_soloed(&mut self, soloed: bool) {
+        self.soloed = soloed;
+    }
+
+    /// Check if the track is soloed.
+    pub fn is_soloed(&self) -> bool {
+        self.soloed
+    }
+
+    /// Get the number of keys.
+    pub fn key_count(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Check if the track has no keys. Its value is then always the
+    /// [default](Track::set_default).
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Count the keys using each interpolation, indexed by the [`Interpolation`]'s numeric value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::track::{Key, Track};
+    /// # use rust_rocket::interpolation::Interpolation;
+    /// let mut track = Track::new("test");
+    /// track.set_key(Key::new(0, 0.0, Interpolation::Step));
+    /// track.set_key(Key::new(5, 1.0, Interpolation::Step));
+    /// track.set_key(Key::new(9, 0.0, Interpolation::Linear));
+    /// let counts = track.interpolation_counts();
+    /// assert_eq!(counts[Interpolation::Step as usize], 2);
+    /// assert_eq!(counts[Interpolation::Linear as usize], 1);
+    /// ```
+    pub fn interpolation_counts(&self) -> [usize; Interpolation::COUNT] {
+        let mut counts = [0; Interpolation::COUNT];
+        for key in &self.keys {
+            counts[key.interpolation as usize] += 1;
+        }
+        counts
+    }
+
+    /// Get all keys, ordered by row.
+    pub fn keys(&self) -> &[Key] {
+        &self.keys
+    }
+
+    /// Get the keys with rows from `start` to `end`, inclusive, ordered by row.
+    ///
+    /// The range is found with a binary search, so this is cheap even for long tracks.
+    /// If `start` is after `end`, the result is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::track::{Key, Track};
+    /// # use rust_rocket::interpolation::Interpolation;
+    /// let keys = (0..100).map(|i| Key::new(i * 10, 0.0, Interpolation::Step));
+    /// let track = Track::from_keys("test", keys);
+    /// let visible = track.keys_in_range(15, 40);
+    /// assert_eq!(visible.len(), 3);
+    /// assert_eq!(visible[0].get_row(), 20);
+    /// ```
+    pub fn keys_in_range(&self, start: Row, end: Row) -> &[Key] {
+        let first = self.keys.partition_point(|k| k.row < start);
+        let last = self.keys.partition_point(|k| k.row <= end);
+        &self.keys[first..last.max(first)]
+    }
+
+    /// Use a custom ease instead of the interpolation of the key at `row`.
+    ///
+    /// The ease is used wherever the key's interpolation would be, as selected by the
+    /// [interpolation source](Track::set_interpolation_source). It's meant for local playback:
+    /// custom eases are not sent to the tracker or [serialized](crate::RocketClient::serialize),
+    /// so the key's interpolation is still used there. Updating the key keeps the ease, deleting it
+    /// removes the ease.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::track::{Key, Track};
+    /// # use rust_rocket::interpolation::Interpolation;
+    /// let mut track = Track::new("test");
+    /// track.set_key(Key::new(0, 0.0, Interpolation::Linear));
+    /// track.set_key(Key::new(10, 1.0, Interpolation::Linear));
+    /// track.set_custom_ease(0, |t: f32| t.sqrt());
+    /// assert_eq!(track.get_value(2.5), 0.5);
+    /// ```
+    pub fn set_custom_ease(&mut self, row: Row, ease: impl Ease + Send + Sync + 'static) {
+        self.custom_eases.insert(row, CustomEase(Arc::new(ease)));
+    }
+
+    /// Stop using a custom ease for the key at `row`, see
+    /// [`set_custom_ease`](Track::set_custom_ease).
+    pub fn remove_custom_ease(&mut self, row: Row) {
+        self.custom_eases.remove(&row);
+    }
+
+    /// Get the row of the last key, or `None` if the track has no keys.
+    pub fn length(&self) -> Option<Row> {
+        self.keys.last().map(|k| k.row)
+    }
+
+    /// Get the row of the first key, or `None` if the track has no keys.
+    pub fn first_row(&self) -> Option<Row> {
+        self.keys.first().map(|k| k.row)
+    }
+
+    fn get_exact_position(&self, row: Row) -> Option<usize> {
         self.keys.iter().position(|k| k.row == row)
     }
 
-    fn get_insert_position(&self, row: u32) -> Option<usize> {
+    fn get_insert_position(&self, row: Row) -> Option<usize> {
         self.keys.iter().position(|k| k.row >= row)
     }
 
-    fn get_lower_bound_position(&self, row: u32) -> usize {
+    /// Get the position of the last key at or before `row`, or `None` if there is none.
+    fn get_lower_bound_position(&self, row: Row) -> Option<usize> {
         self.keys
             .iter()
             .position(|k| k.row > row)
             .unwrap_or(self.keys.len())
-            - 1
+            .checked_sub(1)
+    }
+
+    /// Get the key at a row, if there is one.
+    pub fn get_key(&self, row: Row) -> Option<&Key> {
+        self.get_exact_position(row).map(|pos| &self.keys[pos])
+    }
+
+    /// Check if there is a key at a row, e.g. before overwriting it with
+    /// [`set_key`](Track::set_key).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::track::{Key, Track};
+    /// # use rust_rocket::interpolation::Interpolation;
+    /// let mut track = Track::new("test");
+    /// track.set_key(Key::new(5, 1.0, Interpolation::Step));
+    /// assert!(track.has_key_at(5));
+    /// assert!(!track.has_key_at(4));
+    /// ```
+    pub fn has_key_at(&self, row: Row) -> bool {
+        self.get_exact_position(row).is_some()
+    }
+
+    /// Get the value of the last key strictly before `row`, or `None` if there is none.
+    ///
+    /// Unlike [`get_value`](Track::get_value), this isn't interpolated, which is useful for
+    /// snapping to the previous key in an editor.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::track::{Key, Track};
+    /// # use rust_rocket::interpolation::Interpolation;
+    /// let mut track = Track::new("test");
+    /// track.set_key(Key::new(0, 1.0, Interpolation::Linear));
+    /// track.set_key(Key::new(10, 2.0, Interpolation::Linear));
+    /// assert_eq!(track.value_at_prev_key(10), Some(1.0));
+    /// assert_eq!(track.value_at_next_key(5), Some(2.0));
+    /// assert_eq!(track.value_at_next_key(10), None);
+    /// ```
+    pub fn value_at_prev_key(&self, row: Row) -> Option<f32> {
+        let pos = self.keys.partition_point(|k| k.row < row);
+        pos.checked_sub(1).map(|pos| self.keys[pos].value)
+    }
+
+    /// Get the value of the first key strictly after `row`, or `None` if there is none.
+    ///
+    /// See [`value_at_prev_key`](Track::value_at_prev_key).
+    pub fn value_at_next_key(&self, row: Row) -> Option<f32> {
+        let pos = self.keys.partition_point(|k| k.row <= row);
+        self.keys.get(pos).map(|k| k.value)
     }
 
     /// Insert or update a key on a track.
     pub fn set_key(&mut self, key: Key) {
-        if let Some(pos) = self.get_exact_position(key.row) {
+        // Keys are usually appended in order, e.g. when deserializing
+        if self.keys.last().is_none_or(|k| k.row < key.row) {
+            self.keys.push(key);
+        } else if let Some(pos) = self.get_exact_position(key.row) {
             self.keys[pos] = key;
         } else if let Some(pos) = self.get_insert_position(key.row) {
             self.keys.insert(pos, key);
@@ -89,54 +583,615 @@ impl Track {
     /// Delete a key from a track.
     ///
     /// If a key does not exist this will do nothing.
-    pub fn delete_key(&mut self, row: u32) {
+    pub fn delete_key(&mut self, row: Row) {
         if let Some(pos) = self.get_exact_position(row) {
             self.keys.remove(pos);
         }
+        self.custom_eases.remove(&row);
+    }
+
+    /// Keep only the keys for which `f` returns `true`, like [`Vec::retain`].
+    ///
+    /// Keys stay sorted by row. Custom eases of removed keys are removed too, like with
+    /// [`delete_key`](Track::delete_key).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::track::{Key, Track};
+    /// # use rust_rocket::interpolation::Interpolation;
+    /// let keys = (0..10).map(|row| Key::new(row, 0.0, Interpolation::Step));
+    /// let mut track = Track::from_keys("test", keys);
+    /// // Trim the intro
+    /// track.retain(|key| key.get_row() >= 4);
+    /// assert_eq!(track.first_row(), Some(4));
+    /// ```
+    pub fn retain(&mut self, mut f: impl FnMut(&Key) -> bool) {
+        let custom_eases = &mut self.custom_eases;
+        self.keys.retain(|key| {
+            let keep = f(key);
+            if !keep {
+                custom_eases.remove(&key.row);
+            }
+            keep
+        });
+    }
+
+    /// Remove all keys.
+    #[cfg(feature = "client")]
+    pub(crate) fn clear_keys(&mut self) {
+        self.keys.clear();
+    }
+
+    /// Get a copy of this track with values reflected about `pivot`.
+    ///
+    /// Every key value `v` becomes `2 * pivot - v`, as does the [default](Track::set_default).
+    /// Rows and interpolations are kept, so with `pivot = 0.5`, a ramp from `0` to `1` becomes a
+    /// ramp from `1` to `0`.
+    pub fn inverted(&self, pivot: f32) -> Track {
+        let mut track = self.clone();
+        for key in track.keys.iter_mut() {
+            key.value = 2.0 * pivot - key.value;
+        }
+        track.default = 2.0 * pivot - track.default;
+        track
+    }
+
+    /// Get a copy of this track with key values smoothed by a moving average.
+    ///
+    /// Every key value becomes the mean of the values of the `window / 2` keys on each side and
+    /// itself, so even windows act like the next odd one. Rows don't matter, only the order of
+    /// keys. Near the ends of the track, the window shrinks to stay centered, down to just the key
+    /// itself for the first and last key, so the endpoints keep their values.
+    ///
+    /// Rows and interpolations are kept. Use [`simplify`](Track::simplify) afterwards to remove
+    /// the keys that became redundant.
+    pub fn smoothed(&self, window: usize) -> Track {
+        let mut track = self.clone();
+        let half = window / 2;
+        let n = self.keys.len();
+        for (i, key) in track.keys.iter_mut().enumerate() {
+            let half = half.min(i).min(n - 1 - i);
+            let sum: f64 = self.keys[i - half..=i + half]
+                .iter()
+                .map(|k| f64::from(k.value))
+                .sum();
+            key.value = (sum / (2 * half + 1) as f64) as f32;
+        }
+        track
+    }
+
+    /// Compare the keys of two tracks.
+    ///
+    /// Returns the changes that turn the keys of this track into the keys of `other`, ordered by
+    /// row. Track names and other settings are not compared.
+    pub fn diff(&self, other: &Track) -> Vec<KeyChange> {
+        let mut changes = Vec::new();
+        let mut old = self.keys.iter().peekable();
+        let mut new = other.keys.iter().peekable();
+        loop {
+            match (old.peek(), new.peek()) {
+                (Some(&&o), Some(&&n)) if o.row == n.row => {
+                    if o != n {
+                        changes.push(KeyChange::Modified {
+                            row: o.row,
+                            old: o,
+                            new: n,
+                        });
+                    }
+                    old.next();
+                    new.next();
+                }
+                (Some(&&o), Some(&&n)) if o.row < n.row => {
+                    changes.push(KeyChange::Removed(o.row));
+                    old.next();
+                }
+                (Some(&&o), None) => {
+                    changes.push(KeyChange::Removed(o.row));
+                    old.next();
+                }
+                (_, Some(&&n)) => {
+                    changes.push(KeyChange::Added(n));
+                    new.next();
+                }
+                (None, None) => return changes,
+            }
+        }
     }
 
     /// Get a value based on a row.
     ///
     /// The row can be between two integers.
-    /// This will perform the required interpolation.
+    /// This will perform the required interpolation, using the interpolation of the key selected by
+    /// the [interpolation source](Track::set_interpolation_source).
+    /// If the track has no keys or is [muted](Track::set_muted), the [default](Track::set_default)
+    /// is returned.
+    ///
+    /// Rows outside of the keys are clamped: negative rows and rows before the first key give the
+    /// first key's value, and rows after the last key give the last key's value. This includes rows
+    /// beyond [`Row::MAX`] and infinities.
     pub fn get_value(&self, row: f32) -> f32 {
-        if self.keys.is_empty() {
-            return 0.0;
+        self.get_value_with(row, None)
+    }
+
+    /// Get the value of the last key at or before `row` as an integer, for discrete parameters
+    /// like scene or palette indices.
+    ///
+    /// This ignores interpolation entirely, as if every key used [`Interpolation::Step`], and
+    /// rounds the value to the nearest integer. Rows outside of the keys, muting and the default
+    /// work like in [`get_value`](Track::get_value). Values beyond the range of `i32` saturate,
+    /// and NaN gives `0`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::track::{Key, Track};
+    /// # use rust_rocket::interpolation::Interpolation;
+    /// let mut track = Track::new("scene");
+    /// track.set_key(Key::new(0, 1.0, Interpolation::Linear));
+    /// track.set_key(Key::new(10, 2.0, Interpolation::Linear));
+    /// assert_eq!(track.get_int(9.9), 1);
+    /// assert_eq!(track.get_int(10.), 2);
+    /// ```
+    pub fn get_int(&self, row: f32) -> i32 {
+        self.get_value_with(row, Some(Interpolation::Step)).round() as i32
+    }
+
+    /// Get a function that evaluates this track, like [`get_value`](Track::get_value).
+    ///
+    /// The function borrows the track, so the track can't be edited while it's alive. Samplers can
+    /// be stored by code that only needs values, e.g. boxed as `Box<dyn Fn(f32) -> f32 + '_>`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::track::{Key, Track};
+    /// # use rust_rocket::interpolation::Interpolation;
+    /// let mut track = Track::new("test");
+    /// track.set_key(Key::new(0, 0.0, Interpolation::Linear));
+    /// track.set_key(Key::new(10, 1.0, Interpolation::Linear));
+    ///
+    /// let samplers: Vec<Box<dyn Fn(f32) -> f32 + '_>> = vec![Box::new(track.as_sampler())];
+    /// assert_eq!(samplers[0](5.), 0.5);
+    /// ```
+    pub fn as_sampler(&self) -> impl Fn(f32) -> f32 + '_ {
+        move |row| self.get_value(row)
+    }
+
+    /// Get a value based on a row, like [`get_value`](Track::get_value), clamped to `min..=max`.
+    ///
+    /// A NaN value gives `min`, so the result is always in range, e.g. for shader uniforms.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `min > max`, or either is NaN.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::track::{Key, Track};
+    /// # use rust_rocket::interpolation::Interpolation;
+    /// let mut track = Track::new("test");
+    /// track.set_key(Key::new(0, 0.0, Interpolation::Linear));
+    /// track.set_key(Key::new(10, 2.0, Interpolation::Linear));
+    /// assert_eq!(track.get_value_clamped(5., 0.0, 0.5), 0.5);
+    /// ```
+    pub fn get_value_clamped(&self, row: f32, min: f32, max: f32) -> f32 {
+        let value = self.get_value(row);
+        if value.is_nan() {
+            assert!(min <= max, "min must not be greater than max");
+            min
+        } else {
+            value.clamp(min, max)
+        }
+    }
+
+    /// Get a value based on a row, like [`get_value`](Track::get_value), but using
+    /// `override_interp` instead of the keys' interpolations if it's `Some`.
+    ///
+    /// The track is not changed, which is useful for previewing a different easing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::track::{Key, Track};
+    /// # use rust_rocket::interpolation::Interpolation;
+    /// let mut track = Track::new("test");
+    /// track.set_key(Key::new(0, 0.0, Interpolation::Step));
+    /// track.set_key(Key::new(10, 1.0, Interpolation::Step));
+    /// assert_eq!(track.get_value(5.), 0.0);
+    /// assert_eq!(track.get_value_with(5., Some(Interpolation::Linear)), 0.5);
+    /// ```
+    pub fn get_value_with(&self, row: f32, override_interp: Option<Interpolation>) -> f32 {
+        match self.get_segment_position(f64::from(row)) {
+            SegmentPosition::Outside(value) => value,
+            SegmentPosition::Inside(pos) => {
+                self.get_segment_value(&self.keys[pos], &self.keys[pos + 1], row, override_interp)
+            }
+        }
+    }
+
+    /// Get a value based on an `f64` row, like [`get_value`](Track::get_value).
+    ///
+    /// An `f32` can't represent fractional rows above 2<sup>24</sup> (16777216), so late in long
+    /// demos, `get_value` steps from row to row. This finds the position between the keys in
+    /// `f64`, and only narrows the result. The standard interpolations are evaluated with
+    /// [`Interpolation::interpolate_f64`], custom eases in `f32`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::track::{Key, Track};
+    /// # use rust_rocket::interpolation::Interpolation;
+    /// let mut track = Track::new("test");
+    /// track.set_key(Key::new(16_777_216, 0.0, Interpolation::Linear));
+    /// track.set_key(Key::new(16_777_218, 1.0, Interpolation::Linear));
+    /// assert_eq!(track.get_value_f64(16_777_217.5), 0.75);
+    /// ```
+    pub fn get_value_f64(&self, row: f64) -> f32 {
+        match self.get_segment_position(row) {
+            SegmentPosition::Outside(value) => value,
+            SegmentPosition::Inside(pos) => {
+                let (lower, higher) = (&self.keys[pos], &self.keys[pos + 1]);
+                let t = (row - lower.row as f64) / (higher.row - lower.row) as f64;
+                let key = self.driving_key(lower, higher);
+                if lower.hold || self.custom_eases.contains_key(&key.row) {
+                    return self.ease_segment(lower, higher, t as f32, None);
+                }
+                let it = key.interpolation.interpolate_f64(t);
+                let (from, to) = (f64::from(lower.value), f64::from(higher.value));
+                (from + (to - from) * it) as f32
+            }
+        }
+    }
+
+    /// Find the segment containing `row`, or the value if it's not inside one.
+    fn get_segment_position(&self, row: f64) -> SegmentPosition {
+        if self.keys.is_empty() || self.muted {
+            return SegmentPosition::Outside(self.default);
         }
 
-        let lower_row = row.floor() as u32;
+        // Saturates to 0 for negative rows and to Row::MAX for huge rows
+        let lower_row = row.floor() as Row;
 
-        if lower_row <= self.keys[0].row {
-            return self.keys[0].value;
+        let first = &self.keys[0];
+        if lower_row <= first.row {
+            return SegmentPosition::Outside(first.value);
         }
 
         if lower_row >= self.keys[self.keys.len() - 1].row {
-            return self.keys[self.keys.len() - 1].value;
+            return SegmentPosition::Outside(self.keys[self.keys.len() - 1].value);
+        }
+
+        SegmentPosition::Inside(
+            self.get_lower_bound_position(lower_row)
+                .expect("Row is after the first key"),
+        )
+    }
+
+    /// Get the keys around `row`, for implementing custom interpolation.
+    ///
+    /// Returns the two keys of the segment containing `row`, with the keys before and after them
+    /// if there are any. Returns `None` if `row` is not between the first and the last key, which
+    /// includes tracks with fewer than two keys. At a key's row, the segment starting at the key
+    /// is returned, except at the last key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::track::{Key, Track};
+    /// # use rust_rocket::interpolation::Interpolation;
+    /// let keys = (0..4).map(|i| Key::new(i * 10, i as f32, Interpolation::Linear));
+    /// let track = Track::from_keys("test", keys);
+    /// let (before, lower, higher, after) = track.keys_around(5.).unwrap();
+    /// assert!(before.is_none());
+    /// assert_eq!((lower.get_row(), higher.get_row()), (0, 10));
+    /// assert_eq!(after.unwrap().get_row(), 20);
+    /// ```
+    pub fn keys_around(&self, row: f32) -> Option<(Option<&Key>, &Key, &Key, Option<&Key>)> {
+        let first = self.keys.first()?;
+        let last = self.keys.last()?;
+        if self.keys.len() < 2
+            || f64::from(row) < first.row as f64
+            || f64::from(row) > last.row as f64
+        {
+            return None;
+        }
+
+        let pos = self
+            .get_lower_bound_position(row.floor() as Row)?
+            .min(self.keys.len() - 2);
+        Some((
+            pos.checked_sub(1).map(|before| &self.keys[before]),
+            &self.keys[pos],
+            &self.keys[pos + 1],
+            self.keys.get(pos + 2),
+        ))
+    }
+
+    /// Get the interpolation that drives the segment at `row`, or `None` if the track has no
+    /// keys.
+    ///
+    /// This is the interpolation of the key selected by the
+    /// [interpolation source](Track::set_interpolation_source), like in
+    /// [`get_value`](Track::get_value). Before the first key, the first key's interpolation is
+    /// returned, and after the last key, the last key's. After a [held](Key::with_hold) key,
+    /// [`Interpolation::Step`] is returned. [Custom eases](Track::set_custom_ease) are not
+    /// reflected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_rocket::track::{Key, Track};
+    /// # use rust_rocket::interpolation::Interpolation;
+    /// let mut track = Track::new("test");
+    /// track.set_key(Key::new(0, 0.0, Interpolation::Smooth));
+    /// track.set_key(Key::new(10, 1.0, Interpolation::Step));
+    /// assert!(track.interpolation_at(5.) == Some(Interpolation::Smooth));
+    /// ```
+    pub fn interpolation_at(&self, row: f32) -> Option<Interpolation> {
+        let lower = match self.get_lower_bound_position(row.floor() as Row) {
+            Some(pos) => pos,
+            None => return self.keys.first().map(|k| k.interpolation),
+        };
+        if self.keys[lower].hold {
+            return Some(Interpolation::Step);
+        }
+        let key = match self.interpolation_source {
+            InterpolationSource::SegmentStart => &self.keys[lower],
+            InterpolationSource::SegmentEnd => {
+                self.keys.get(lower + 1).unwrap_or(&self.keys[lower])
+            }
+        };
+        Some(key.interpolation)
+    }
+
+    /// Get values at `count` rows, starting at `start` and advancing by `step` rows.
+    ///
+    /// The value at index `i` is exactly [`get_value(start + step * i as f32)`](Track::get_value).
+    pub fn bake(&self, start: f32, step: f32, count: usize) -> Vec<f32> {
+        (0..count)
+            .map(|i| self.get_value(start + step * i as f32))
+            .collect()
+    }
+
+    /// Get values at `count` rows, starting at `start` and advancing by `step` rows.
+    ///
+    /// This returns exactly the same values as [`bake`](Track::bake), but much faster for long
+    /// bakes. When `step` is not negative, the rows only move forward, so the segment of each row
+    /// is found by advancing a cursor over the keys instead of searching for every row. All rows
+    /// of a segment are then evaluated in groups of 8, with the segment's interpolation inlined, so
+    /// the compiler can vectorize them. [Custom eases](Track::set_custom_ease) are evaluated one
+    /// row at a time, and negative or NaN steps fall back to `bake`.
+    #[cfg(feature = "simd")]
+    pub fn bake_simd(&self, start: f32, step: f32, count: usize) -> Vec<f32> {
+        if self.muted || self.keys.is_empty() || step < 0.0 || step.is_nan() {
+            return self.bake(start, step, count);
         }
 
-        let pos = self.get_lower_bound_position(lower_row);
+        let row_at = |i: usize| start + step * i as f32;
+        // Saturates like in `get_value`, and never decreases as `i` increases
+        let lower_row_at = |i: usize| row_at(i).floor() as Row;
 
-        let lower = &self.keys[pos];
-        let higher = &self.keys[pos + 1];
+        let first = &self.keys[0];
+        let last = &self.keys[self.keys.len() - 1];
+        let mut values = Vec::with_capacity(count);
+        let mut i = partition_point(0..count, |i| lower_row_at(i) <= first.row);
+        values.resize(i, first.value);
 
+        let mut lower = 0;
+        while i < count {
+            let lower_row = lower_row_at(i);
+            if lower_row >= last.row {
+                break;
+            }
+            while self.keys[lower + 1].row <= lower_row {
+                lower += 1;
+            }
+            let (lower, higher) = (&self.keys[lower], &self.keys[lower + 1]);
+            let end = partition_point(i..count, |i| lower_row_at(i) < higher.row);
+            self.bake_segment(lower, higher, i..end, row_at, &mut values);
+            i = end;
+        }
+        values.resize(count, last.value);
+        values
+    }
+
+    /// Append the values at `row_at(i)` for each `i` in `indices` to `values`, where all the rows
+    /// are interpolated between `lower` and `higher`.
+    #[cfg(feature = "simd")]
+    fn bake_segment(
+        &self,
+        lower: &Key,
+        higher: &Key,
+        indices: std::ops::Range<usize>,
+        row_at: impl Fn(usize) -> f32,
+        values: &mut Vec<f32>,
+    ) {
+        if lower.hold {
+            values.resize(values.len() + indices.len(), lower.value);
+            return;
+        }
+        let key = self.driving_key(lower, higher);
+        if self.custom_eases.contains_key(&key.row) {
+            values.extend(indices.map(|i| self.get_segment_value(lower, higher, row_at(i), None)));
+            return;
+        }
+
+        // The same operations as `get_segment_value` and `Interpolation::interpolate`
+        let rows = (lower.row as f32, (higher.row as f32) - (lower.row as f32));
+        let segment = (lower.value, higher.value - lower.value);
+        match key.interpolation {
+            Interpolation::Step => bake_lanes(indices, row_at, rows, segment, |_| 0.0, values),
+            Interpolation::Linear => bake_lanes(indices, row_at, rows, segment, |t| t, values),
+            Interpolation::Smooth => bake_lanes(
+                indices,
+                row_at,
+                rows,
+                segment,
+                |t| t * t * (3.0 - 2.0 * t),
+                values,
+            ),
+            Interpolation::Ramp => bake_lanes(indices, row_at, rows, segment, |t| t * t, values),
+        }
+    }
+
+    /// Get a value between two keys, where `lower.row <= row <= higher.row`.
+    ///
+    /// At `higher.row` this is the value just before `higher`, which differs from `higher.value`
+    /// for [`Interpolation::Step`].
+    ///
+    /// Segments are never zero-width: keys are kept unique per row, and even if several keys had
+    /// the same row, [`get_lower_bound_position`](Track::get_lower_bound_position) picks the last
+    /// of them as `lower`, so `higher` is always at a later row.
+    fn get_segment_value(
+        &self,
+        lower: &Key,
+        higher: &Key,
+        row: f32,
+        override_interp: Option<Interpolation>,
+    ) -> f32 {
+        debug_assert!(lower.row < higher.row, "Zero-width segment");
         let t = (row - (lower.row as f32)) / ((higher.row as f32) - (lower.row as f32));
-        let it = lower.interpolation.interpolate(t);
+        self.ease_segment(lower, higher, t, override_interp)
+    }
 
-        (lower.value as f32) + ((higher.value as f32) - (lower.value as f32)) * it
+    /// Get a value between two keys at position `t`, from `0` at `lower` to `1` at `higher`.
+    fn ease_segment(
+        &self,
+        lower: &Key,
+        higher: &Key,
+        t: f32,
+        override_interp: Option<Interpolation>,
+    ) -> f32 {
+        if lower.hold {
+            return lower.value;
+        }
+        let key = self.driving_key(lower, higher);
+        let ease: &dyn Ease = match override_interp {
+            Some(ref interpolation) => interpolation,
+            None => match self.custom_eases.get(&key.row) {
+                Some(custom) => &*custom.0,
+                None => &key.interpolation,
+            },
+        };
+
+        let it = ease.ease(t);
+
+        lower.value + (higher.value - lower.value) * it
     }
 
-    #[cfg(feature = "client")]
-    pub(crate) fn serialize(&self, wtr: &mut Vec<u8>) {
-        
-        wtr.write_u64::<LE>(self.get_name().len() as u64).unwrap();
-        wtr.write(self.get_name().as_bytes()).unwrap();
-        wtr.write_u64::<LE>(self.keys.len() as u64).unwrap();
-        for k in self.keys.iter() {
-            wtr.write_u32::<LE>(k.row).unwrap();
-            wtr.write_f32::<LE>(k.value).unwrap();
-            wtr.write_u32::<LE>(k.interpolation as u32).unwrap();
+    /// Get the key whose interpolation is used between `lower` and `higher`.
+    fn driving_key<'a>(&self, lower: &'a Key, higher: &'a Key) -> &'a Key {
+        match self.interpolation_source {
+            InterpolationSource::SegmentStart => lower,
+            InterpolationSource::SegmentEnd => higher,
+        }
+    }
+
+    /// Remove keys that don't change the track's values by more than `tolerance`.
+    ///
+    /// This uses the Ramer–Douglas–Peucker algorithm: a key is kept only when the values produced
+    /// by interpolating directly between the kept keys around it would deviate from the original
+    /// by more than `tolerance`. Deviation is measured at the start, middle and end of every
+    /// original segment, which also catches the jumps of [`Interpolation::Step`] segments.
+    ///
+    /// The first and the last key are always kept.
+    pub fn simplify(&mut self, tolerance: f32) {
+        if self.keys.len() < 3 {
+            return;
+        }
 
+        let mut keep = vec![false; self.keys.len()];
+        keep[0] = true;
+        keep[self.keys.len() - 1] = true;
+
+        let mut spans = vec![(0, self.keys.len() - 1)];
+        while let Some((first, last)) = spans.pop() {
+            let worst = (first + 1..last)
+                .map(|i| (i, self.get_simplify_error(first, last, i)))
+                .max_by(|a, b| a.1.total_cmp(&b.1));
+
+            if let Some((i, error)) = worst {
+                if error > tolerance {
+                    keep[i] = true;
+                    spans.push((first, i));
+                    spans.push((i, last));
+                }
+            }
         }
+
+        // Custom eases of removed keys are removed too
+        let mut keep = keep.into_iter();
+        self.retain(|_| keep.next().unwrap());
+    }
+
+    /// Get the largest deviation on the segments around key `i` when interpolating directly from
+    /// key `first` to key `last` instead.
+    fn get_simplify_error(&self, first: usize, last: usize, i: usize) -> f32 {
+        let (first, last) = (&self.keys[first], &self.keys[last]);
+        [(i - 1, i), (i, i + 1)]
+            .iter()
+            .flat_map(|&(a, b)| {
+                let (lower, higher) = (&self.keys[a], &self.keys[b]);
+                [0.0, 0.5, 1.0].iter().map(move |t| {
+                    let row = lower.row as f32 + (higher.row - lower.row) as f32 * t;
+                    (self.get_segment_value(lower, higher, row, None)
+                        - self.get_segment_value(first, last, row, None))
+                    .abs()
+                })
+            })
+            .fold(0.0, f32::max)
+    }
+}
+
+/// How many rows [`Track::bake_simd`] evaluates together.
+#[cfg(feature = "simd")]
+const BAKE_LANES: usize = 8;
+
+/// Find the first index in `range` for which `pred` is false. `pred` must be true for all indices
+/// before it, and false for all indices after it.
+#[cfg(feature = "simd")]
+fn partition_point(range: std::ops::Range<usize>, pred: impl Fn(usize) -> bool) -> usize {
+    let (mut low, mut high) = (range.start, range.end);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if pred(mid) {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
+
+/// Append eased values at `row_at(i)` for each `i` in `indices` to `values`, [`BAKE_LANES`] at a
+/// time. `rows` is the start and the length of the segment, and `segment` the start value and
+/// the difference to the end value.
+#[cfg(feature = "simd")]
+fn bake_lanes(
+    indices: std::ops::Range<usize>,
+    row_at: impl Fn(usize) -> f32,
+    (start_row, length): (f32, f32),
+    (start_value, difference): (f32, f32),
+    ease: impl Fn(f32) -> f32,
+    values: &mut Vec<f32>,
+) {
+    let value = |row: f32| start_value + difference * ease((row - start_row) / length);
+
+    let offset = values.len();
+    values.resize(offset + indices.len(), 0.0);
+    let mut chunks = values[offset..].chunks_exact_mut(BAKE_LANES);
+    let mut i = indices.start;
+    for chunk in &mut chunks {
+        let rows: [f32; BAKE_LANES] = std::array::from_fn(|lane| row_at(i + lane));
+        for lane in 0..BAKE_LANES {
+            chunk[lane] = value(rows[lane]);
+        }
+        i += BAKE_LANES;
+    }
+    for (v, i) in chunks.into_remainder().iter_mut().zip(i..) {
+        *v = value(row_at(i));
     }
 }
 
@@ -163,4 +1218,437 @@ mod tests {
         assert_eq!(track.get_value(10.), 1.0);
         assert_eq!(track.get_value(11.), 1.0);
     }
+
+    #[test]
+    fn test_default() {
+        let mut track = Track::new("test");
+        assert_eq!(track.get_value(0.), 0.0);
+
+        track.set_default(1.0);
+        assert_eq!(track.get_value(0.), 1.0);
+
+        track.set_key(Key::new(0, 0.5, Interpolation::Step));
+        assert_eq!(track.get_value(0.), 0.5);
+        assert_eq!(track.get_value(10.), 0.5);
+    }
+
+    #[test]
+    fn test_value_f64() {
+        let track = Track::from_keys(
+            "test",
+            vec![
+                Key::new(0, 0.0, Interpolation::Linear),
+                Key::new(10, 1.0, Interpolation::Step),
+            ],
+        );
+        for &row in &[-1.0, 0.0, 0.5, 2.5, 10.0, 1e12] {
+            assert_eq!(track.get_value_f64(row), track.get_value(row as f32));
+        }
+    }
+
+    #[test]
+    fn test_hold() {
+        let mut track = Track::new("test");
+        track.set_key(Key::new(0, 0.0, Interpolation::Linear));
+        track.set_key(Key::new(10, 1.0, Interpolation::Linear).with_hold(true));
+        track.set_key(Key::new(20, 0.0, Interpolation::Linear));
+        track.set_custom_ease(10, |t: f32| t);
+
+        assert_eq!(track.get_value(5.), 0.5);
+        assert_eq!(track.get_value(15.), 1.0);
+        assert_eq!(track.get_value_f64(15.), 1.0);
+        assert_eq!(track.get_value_with(15., Some(Interpolation::Linear)), 1.0);
+        assert!(track.interpolation_at(15.) == Some(Interpolation::Step));
+        assert_eq!(track.get_value(20.), 0.0);
+
+        track.set_key(Key::new(10, 1.0, Interpolation::Linear));
+        assert_eq!(track.get_value(15.), 0.5);
+    }
+
+    #[test]
+    fn test_value_clamped() {
+        let mut track = Track::new("test");
+        track.set_key(Key::new(0, -1.0, Interpolation::Smooth));
+        track.set_key(Key::new(10, 1.0, Interpolation::Step));
+        track.set_key(Key::new(20, f32::NAN, Interpolation::Step));
+
+        assert_eq!(track.get_value_clamped(0., 0.0, 1.0), 0.0);
+        assert_eq!(track.get_value_clamped(5., 0.0, 1.0), 0.0);
+        assert_eq!(track.get_value_clamped(5., -0.5, -0.25), -0.25);
+        // Step segments multiply the difference to a NaN key by 0, which is NaN
+        assert_eq!(track.get_value_clamped(10., 0.0, 1.0), 0.0);
+    }
+
+    #[test]
+    fn test_equal_rows() {
+        // Tracks keep keys unique per row, so this can only be built by hand
+        let mut track = Track::new("test");
+        track.keys = vec![
+            Key::new(0, 0.0, Interpolation::Linear),
+            Key::new(5, 1.0, Interpolation::Linear),
+            Key::new(5, 2.0, Interpolation::Linear),
+            Key::new(10, 3.0, Interpolation::Linear),
+        ];
+        assert_eq!(track.get_value(4.5), 0.9);
+        assert_eq!(track.get_value(5.), 2.0);
+        assert_eq!(track.get_value_f64(7.5), 2.5);
+
+        track.keys.drain(..1);
+        track.keys.pop();
+        for &row in &[4.0, 5.0, 5.5, 6.0] {
+            assert!(track.get_value(row).is_finite());
+            assert!(track.get_value_f64(f64::from(row)).is_finite());
+        }
+        // The whole row of the first key gives the first key's value
+        assert_eq!(track.get_value(5.5), 1.0);
+    }
+
+    #[test]
+    fn test_muted() {
+        let mut track = Track::new("test");
+        track.set_default(0.25);
+        track.set_key(Key::new(0, 1.0, Interpolation::Linear));
+        track.set_key(Key::new(10, 2.0, Interpolation::Step));
+
+        track.set_muted(true);
+        assert_eq!(track.get_value(5.), 0.25);
+        assert_eq!(track.bake(0., 5., 3), [0.25; 3]);
+
+        track.set_muted(false);
+        assert_eq!(track.get_value(5.), 1.5);
+    }
+
+    #[test]
+    fn test_custom_ease() {
+        let mut track = Track::new("test");
+        track.set_key(Key::new(0, 0.0, Interpolation::Step));
+        track.set_key(Key::new(10, 1.0, Interpolation::Step));
+        track.set_custom_ease(0, |t: f32| 1.0 - t);
+
+        assert_eq!(track.get_value(2.), 0.8);
+        assert_eq!(track.get_value_with(2., Some(Interpolation::Linear)), 0.2);
+        assert_eq!(track.clone().get_value(2.), 0.8);
+
+        track.set_key(Key::new(0, 0.0, Interpolation::Linear));
+        assert_eq!(track.get_value(2.), 0.8);
+
+        track.delete_key(0);
+        track.set_key(Key::new(0, 0.0, Interpolation::Step));
+        assert_eq!(track.get_value(2.), 0.0);
+    }
+
+    #[test]
+    fn test_from_fn() {
+        let track = Track::from_fn("ramp", [10, 0, 5], Interpolation::Linear, |row| {
+            row as f32 * 2.0
+        });
+        assert_eq!(track.first_row(), Some(0));
+        assert_eq!(track.get_value(2.5), 5.0);
+        assert_eq!(track.get_value(10.), 20.0);
+    }
+
+    #[test]
+    fn test_keys_in_range() {
+        let track = Track::from_keys(
+            "test",
+            [0, 5, 10, 15].map(|row| Key::new(row, 0.0, Interpolation::Step)),
+        );
+        let rows = |keys: &[Key]| keys.iter().map(Key::get_row).collect::<Vec<_>>();
+
+        assert_eq!(rows(track.keys_in_range(5, 10)), [5, 10]);
+        assert_eq!(rows(track.keys_in_range(6, 9)), [] as [Row; 0]);
+        assert_eq!(rows(track.keys_in_range(11, Row::MAX)), [15]);
+        assert_eq!(rows(track.keys_in_range(10, 5)), [] as [Row; 0]);
+        assert!(track.keys_in_range(0, Row::MAX) == track.keys());
+    }
+
+    #[test]
+    fn test_value_at_adjacent_keys() {
+        let track = Track::from_keys(
+            "test",
+            [0, 5, 10].map(|row| Key::new(row, row as f32, Interpolation::Linear)),
+        );
+
+        assert_eq!(track.value_at_prev_key(0), None);
+        assert_eq!(track.value_at_prev_key(5), Some(0.0));
+        assert_eq!(track.value_at_prev_key(7), Some(5.0));
+        assert_eq!(track.value_at_prev_key(Row::MAX), Some(10.0));
+        assert_eq!(track.value_at_next_key(0), Some(5.0));
+        assert_eq!(track.value_at_next_key(7), Some(10.0));
+        assert_eq!(track.value_at_next_key(10), None);
+        assert_eq!(Track::new("test").value_at_next_key(0), None);
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut track = Track::from_keys(
+            "test",
+            [0, 5, 10, 15].map(|row| Key::new(row, 0.0, Interpolation::Step)),
+        );
+        track.set_custom_ease(0, |t: f32| t);
+        track.retain(|key| (5..=10).contains(&key.get_row()));
+
+        let rows: Vec<_> = track.keys().iter().map(Key::get_row).collect();
+        assert_eq!(rows, [5, 10]);
+        assert!(track.custom_eases.is_empty());
+    }
+
+    #[test]
+    fn test_keys_around() {
+        let track = Track::from_keys(
+            "test",
+            [0, 10, 20].map(|row| Key::new(row, 0.0, Interpolation::Linear)),
+        );
+        let rows = |row| {
+            let (before, lower, higher, after) = track.keys_around(row).unwrap();
+            (
+                before.map(Key::get_row),
+                lower.get_row(),
+                higher.get_row(),
+                after.map(Key::get_row),
+            )
+        };
+
+        assert_eq!(rows(0.), (None, 0, 10, Some(20)));
+        assert_eq!(rows(10.), (Some(0), 10, 20, None));
+        assert_eq!(rows(20.), (Some(0), 10, 20, None));
+        assert!(track.keys_around(-0.5).is_none());
+        assert!(track.keys_around(20.5).is_none());
+        assert!(Track::new("empty").keys_around(0.).is_none());
+    }
+
+    #[test]
+    fn test_interpolation_at() {
+        let mut track = Track::new("test");
+        assert!(track.interpolation_at(0.).is_none());
+
+        track.set_key(Key::new(5, 0.0, Interpolation::Linear));
+        track.set_key(Key::new(10, 1.0, Interpolation::Ramp));
+        let at = |track: &Track, row| track.interpolation_at(row).unwrap();
+        assert!(at(&track, -1.) == Interpolation::Linear);
+        assert!(at(&track, 9.5) == Interpolation::Linear);
+        assert!(at(&track, 10.) == Interpolation::Ramp);
+        assert!(at(&track, 20.) == Interpolation::Ramp);
+
+        track.set_interpolation_source(InterpolationSource::SegmentEnd);
+        assert!(at(&track, 5.) == Interpolation::Ramp);
+    }
+
+    #[test]
+    fn test_interpolation_source() {
+        let mut track = Track::new("test");
+        track.set_key(Key::new(0, 0.0, Interpolation::Step));
+        track.set_key(Key::new(10, 1.0, Interpolation::Linear));
+
+        assert_eq!(track.get_value(5.), 0.0);
+
+        track.set_interpolation_source(InterpolationSource::SegmentEnd);
+        assert_eq!(track.get_value(5.), 0.5);
+        assert_eq!(track.get_value(10.), 1.0);
+    }
+
+    #[test]
+    fn test_simplify() {
+        let mut track = Track::new("test");
+        for row in 0..=10 {
+            track.set_key(Key::new(row, row as f32, Interpolation::Linear));
+        }
+        track.set_key(Key::new(20, 0.0, Interpolation::Step));
+        track.set_key(Key::new(25, 0.0, Interpolation::Linear));
+        track.set_key(Key::new(30, 1.0, Interpolation::Step));
+
+        track.simplify(0.001);
+        let rows: Vec<Row> = track.keys.iter().map(|k| k.row).collect();
+        assert_eq!(rows, [0, 10, 20, 25, 30]);
+
+        track.simplify(100.);
+        let rows: Vec<Row> = track.keys.iter().map(|k| k.row).collect();
+        assert_eq!(rows, [0, 30]);
+    }
+
+    #[test]
+    fn test_simplify_custom_ease() {
+        let mut track = Track::new("test");
+        for row in [0, 10, 20] {
+            track.set_key(Key::new(row, row as f32, Interpolation::Linear));
+        }
+        // Linear at the rows that simplify checks
+        track.set_custom_ease(10, |t: f32| t + t * (1.0 - t) * (2.0 * t - 1.0));
+
+        track.simplify(0.001);
+        assert!(!track.has_key_at(10));
+        assert!(track.custom_eases.is_empty());
+
+        track.set_key(Key::new(10, 10.0, Interpolation::Linear));
+        assert_eq!(track.get_value(12.5), 12.5);
+    }
+
+    #[test]
+    fn test_inverted() {
+        let mut track = Track::new("test");
+        track.set_key(Key::new(0, 0.0, Interpolation::Smooth));
+        track.set_key(Key::new(10, 1.0, Interpolation::Step));
+
+        let inverted = track.inverted(0.5);
+        assert_eq!(inverted.get_value(0.), 1.0);
+        assert_eq!(inverted.get_value(10.), 0.0);
+        for row in 0..10 {
+            let row = row as f32;
+            assert_eq!(inverted.get_value(row), 1.0 - track.get_value(row));
+        }
+    }
+
+    #[test]
+    fn test_smoothed() {
+        let values = [0.0, 1.0, 0.0, 1.0, 0.0, 4.0];
+        let track = Track::from_keys(
+            "test",
+            values
+                .iter()
+                .enumerate()
+                .map(|(row, &value)| Key::new(row as Row * 2, value, Interpolation::Linear)),
+        );
+
+        let smoothed = track.smoothed(3);
+        let values: Vec<f32> = smoothed.keys.iter().map(|k| k.value).collect();
+        assert_eq!(
+            values,
+            [0.0, 1.0 / 3.0, 2.0 / 3.0, 1.0 / 3.0, 5.0 / 3.0, 4.0]
+        );
+        assert!(smoothed
+            .keys
+            .iter()
+            .zip(&track.keys)
+            .all(|(a, b)| a.row == b.row && a.interpolation == b.interpolation));
+
+        // The window shrinks near the ends, and even windows act like the next odd one
+        let values: Vec<f32> = track.smoothed(4).keys.iter().map(|k| k.value).collect();
+        assert_eq!(values, [0.0, 1.0 / 3.0, 0.4, 1.2, 5.0 / 3.0, 4.0]);
+        assert!(track.smoothed(1).keys == track.keys);
+        assert_eq!(Track::new("test").smoothed(5).key_count(), 0);
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut old = Track::new("test");
+        old.set_key(Key::new(0, 0.0, Interpolation::Step));
+        old.set_key(Key::new(5, 1.0, Interpolation::Step));
+        old.set_key(Key::new(10, 2.0, Interpolation::Step));
+
+        let mut new = old.clone();
+        new.delete_key(0);
+        new.set_key(Key::new(5, 1.0, Interpolation::Linear));
+        new.set_key(Key::new(15, 3.0, Interpolation::Step));
+
+        assert!(old.diff(&old).is_empty());
+        assert!(
+            old.diff(&new)
+                == [
+                    KeyChange::Removed(0),
+                    KeyChange::Modified {
+                        row: 5,
+                        old: Key::new(5, 1.0, Interpolation::Step),
+                        new: Key::new(5, 1.0, Interpolation::Linear),
+                    },
+                    KeyChange::Added(Key::new(15, 3.0, Interpolation::Step)),
+                ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "simd")]
+    fn test_bake_simd() {
+        let mut track = Track::new("test");
+        for (i, interpolation) in Interpolation::all().iter().enumerate() {
+            let row = i as Row * 7 + 3;
+            track.set_key(Key::new(row, (row as f32).sin(), *interpolation));
+        }
+        track.set_key(Key::new(40, 2.0, Interpolation::Linear).with_hold(true));
+        track.set_key(Key::new(45, -1.0, Interpolation::Linear));
+        track.set_custom_ease(45, |t: f32| t.sqrt());
+        track.set_key(Key::new(50, 0.5, Interpolation::Smooth));
+
+        let cases = [
+            (-5.0, 0.1, 1000),
+            (0.0, 0.013, 4001),
+            (3.0, 1.0, 60),
+            (40.0, -0.37, 301),
+            (0.0, 0.0, 9),
+            (f32::NAN, 1.0, 9),
+            (0.0, f32::INFINITY, 9),
+        ];
+        for &(start, step, count) in &cases {
+            let scalar = track.bake(start, step, count);
+            let simd = track.bake_simd(start, step, count);
+            assert_eq!(scalar.len(), count);
+            assert_eq!(simd.len(), count);
+            assert!(scalar
+                .iter()
+                .zip(&simd)
+                .all(|(a, b)| a.to_bits() == b.to_bits()));
+        }
+    }
+
+    #[test]
+    fn test_lower_bound_position() {
+        let mut track = Track::new("test");
+        assert_eq!(track.get_lower_bound_position(0), None);
+
+        track.set_key(Key::new(5, 0.0, Interpolation::Step));
+        track.set_key(Key::new(10, 1.0, Interpolation::Step));
+        assert_eq!(track.get_lower_bound_position(0), None);
+        assert_eq!(track.get_lower_bound_position(4), None);
+        assert_eq!(track.get_lower_bound_position(5), Some(0));
+        assert_eq!(track.get_lower_bound_position(9), Some(0));
+        assert_eq!(track.get_lower_bound_position(10), Some(1));
+        assert_eq!(track.get_lower_bound_position(Row::MAX), Some(1));
+    }
+
+    #[test]
+    fn test_from_keys_last_wins() {
+        let track = Track::from_keys(
+            "test",
+            vec![
+                Key::new(5, 1.0, Interpolation::Step),
+                Key::new(0, 0.0, Interpolation::Step),
+                Key::new(5, 2.0, Interpolation::Step),
+            ],
+        );
+        let rows: Vec<Row> = track.keys.iter().map(|k| k.row).collect();
+        assert_eq!(rows, [0, 5]);
+        assert_eq!(track.get_value(5.), 2.0);
+    }
+
+    #[test]
+    fn test_out_of_range_rows() {
+        let track = Track::from_keys(
+            "test",
+            vec![
+                Key::new(0, 0.0, Interpolation::Linear),
+                Key::new(10, 1.0, Interpolation::Step),
+            ],
+        );
+
+        // Negative rows saturate to row 0
+        assert_eq!(track.get_value(-0.001), 0.0);
+        assert_eq!(track.get_value(-1e9), 0.0);
+        assert_eq!(track.get_value(f32::NEG_INFINITY), 0.0);
+        // Huge rows saturate to Row::MAX, which is after the last key
+        assert_eq!(track.get_value(1e12), 1.0);
+        assert_eq!(track.get_value(f32::INFINITY), 1.0);
+    }
+
+    #[test]
+    fn test_wide_rows() {
+        let end = 1 << 33;
+        let track = Track::from_keys(
+            "test",
+            vec![
+                Key::new(0, 0.0, Interpolation::Linear),
+                Key::new(end, 1.0, Interpolation::Step),
+            ],
+        );
+        assert_eq!(track.length(), Some(end));
+        assert_eq!(track.get_value_f64((1u64 << 32) as f64), 0.5);
+    }
 }